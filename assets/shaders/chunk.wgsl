@@ -31,9 +31,57 @@ struct ChunkMaterial {
 
 @group(2) @binding(0) var<uniform> chunk_material: ChunkMaterial;
 
+#ifdef PACKED_QUADS
+// packed u64 quads as (low, high) bits, see src/packed_quad.rs
+@group(2) @binding(1) var<storage, read> quads: array<vec2<u32>>;
+
+// expands a packed quad into the vert_data of one of its 6 vertices,
+// the gpu version of packed_quad::quad_vertex
+fn packed_quad_vertex(quad: vec2<u32>, vertex_index: u32) -> u32 {
+    var corners = array<u32, 6>(0u, 1u, 2u, 0u, 2u, 3u);
+    let origin = vec3<u32>(quad.x & 63u, quad.x >> 6u & 63u, quad.x >> 12u & 63u);
+    let w = quad.x >> 18u & 63u;
+    let h = quad.x >> 24u & 63u;
+    let normal_index = quad.y & 7u;
+    let block_index = quad.y >> 3u & 127u;
+    let rotate = quad.y >> 18u & 1u;
+    let is_fluid = quad.y >> 19u & 1u;
+
+    // up, right and forward faces are wound in reverse
+    let k = (corners[vertex_index] + rotate) % 4u;
+    var corner = k;
+    if normal_index == 1u || normal_index == 3u || normal_index == 4u {
+        corner = (4u - k) % 4u;
+    }
+    var u = vec3<u32>(1u, 0u, 0u);
+    var v = vec3<u32>(0u, 1u, 0u);
+    if normal_index < 2u {
+        u = vec3<u32>(0u, 0u, 1u);
+    } else if normal_index < 4u {
+        v = vec3<u32>(0u, 0u, 1u);
+    }
+    let pos = origin
+        + u * select(0u, w, corner == 1u || corner == 2u)
+        + v * select(0u, h, corner >= 2u);
+
+    var ao = (quad.y >> (10u + corner * 2u)) & 3u;
+    if is_fluid == 1u {
+        // fluids lower the corners above their voxel
+        let voxel_y = origin.y - select(0u, 1u, normal_index == 3u);
+        ao = select(0u, (quad.y >> 20u) & 7u, pos.y > voxel_y);
+    }
+    return pos.x | (pos.y << 6u) | (pos.z << 12u) | (ao << 18u) | (normal_index << 21u)
+        | (is_fluid << 24u) | (block_index << 25u);
+}
+#endif
+
 struct Vertex {
     @builtin(instance_index) instance_index: u32,
+#ifdef PACKED_QUADS
+    @builtin(vertex_index) vertex_index: u32,
+#else
     @location(0) vert_data: u32,
+#endif
     // @location(1) blend_color: vec4<f32>,
 };
 
@@ -65,10 +113,14 @@ var<private> normals: array<vec3<f32>,6> = array<vec3<f32>,6> (
 	vec3<f32>(0.0, 0.0, 1.0) // Back
 );
 
-var<private> block_color: array<vec3<f32>,3> = array<vec3<f32>,3> (
+var<private> block_color: array<vec3<f32>,7> = array<vec3<f32>,7> (
 	vec3<f32>(0.0, 0.0, 0.0), // air
 	vec3<f32>(0.0, 1.0, 0.0), // grass
 	vec3<f32>(0.3, 0.4, 0.0), // dirt
+	vec3<f32>(0.1, 0.3, 0.9), // water
+	vec3<f32>(1.0, 0.35, 0.0), // lava
+	vec3<f32>(0.9, 0.8, 0.5), // sand
+	vec3<f32>(0.45, 0.42, 0.4), // gravel
 );
 
 
@@ -80,15 +132,26 @@ fn x_positive_bits(bits: u32) -> u32{
 fn vertex(vertex: Vertex) -> VertexOutput {
     var out: VertexOutput;
 
-    let x = f32(vertex.vert_data & x_positive_bits(6u));
-    let y = f32(vertex.vert_data >> 6u & x_positive_bits(6u));
-    let z = f32(vertex.vert_data >> 12u & x_positive_bits(6u));
-    let ao = vertex.vert_data >> 18u & x_positive_bits(3u);
-    let normal_index = vertex.vert_data >> 21u & x_positive_bits(3u);
-    let block_index = vertex.vert_data >> 25u & x_positive_bits(7u);
+#ifdef PACKED_QUADS
+    let vert_data = packed_quad_vertex(quads[vertex.vertex_index / 6u], vertex.vertex_index % 6u);
+#else
+    let vert_data = vertex.vert_data;
+#endif
+
+    let x = f32(vert_data & x_positive_bits(6u));
+    let y = f32(vert_data >> 6u & x_positive_bits(6u));
+    let z = f32(vert_data >> 12u & x_positive_bits(6u));
+    let ao = vert_data >> 18u & x_positive_bits(3u);
+    let normal_index = vert_data >> 21u & x_positive_bits(3u);
+    let is_fluid = (vert_data >> 24u & 1u) == 1u;
+    let block_index = vert_data >> 25u & x_positive_bits(7u);
     // let normal_index: u32 = (vertex.v_pos_6b_normal_3b_texid_8b & 1835008u) >> 18u;
 
-    let local_position = vec4<f32>(x,y,z, 1.0);
+    // fluids store how far the vertex is lowered in the ao bits, in 1/8ths of a voxel
+    var local_position = vec4<f32>(x,y,z, 1.0);
+    if is_fluid {
+        local_position.y -= f32(ao) / 8.0;
+    }
     let world_position = get_model_matrix(vertex.instance_index) * local_position;
     out.clip_position = mesh_position_local_to_clip(
         get_model_matrix(vertex.instance_index),
@@ -96,7 +159,7 @@ fn vertex(vertex: Vertex) -> VertexOutput {
     );
 
     let ambient_lerp = ambient_lerps[ao];
-    out.ambient = ambient_lerp;
+    out.ambient = select(ambient_lerp, 1.0, is_fluid);
     out.world_position = world_position;
     // out.world_normal = vec3<f32>(0.0,1.0,0.0);
 
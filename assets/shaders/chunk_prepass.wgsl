@@ -24,13 +24,61 @@ struct ChunkMaterial {
 
 @group(2) @binding(0) var<uniform> material: ChunkMaterial;
 
+#ifdef PACKED_QUADS
+// packed u64 quads as (low, high) bits, see src/packed_quad.rs
+@group(2) @binding(1) var<storage, read> quads: array<vec2<u32>>;
+
+// expands a packed quad into the vert_data of one of its 6 vertices,
+// the gpu version of packed_quad::quad_vertex
+fn packed_quad_vertex(quad: vec2<u32>, vertex_index: u32) -> u32 {
+    var corners = array<u32, 6>(0u, 1u, 2u, 0u, 2u, 3u);
+    let origin = vec3<u32>(quad.x & 63u, quad.x >> 6u & 63u, quad.x >> 12u & 63u);
+    let w = quad.x >> 18u & 63u;
+    let h = quad.x >> 24u & 63u;
+    let normal_index = quad.y & 7u;
+    let block_index = quad.y >> 3u & 127u;
+    let rotate = quad.y >> 18u & 1u;
+    let is_fluid = quad.y >> 19u & 1u;
+
+    // up, right and forward faces are wound in reverse
+    let k = (corners[vertex_index] + rotate) % 4u;
+    var corner = k;
+    if normal_index == 1u || normal_index == 3u || normal_index == 4u {
+        corner = (4u - k) % 4u;
+    }
+    var u = vec3<u32>(1u, 0u, 0u);
+    var v = vec3<u32>(0u, 1u, 0u);
+    if normal_index < 2u {
+        u = vec3<u32>(0u, 0u, 1u);
+    } else if normal_index < 4u {
+        v = vec3<u32>(0u, 0u, 1u);
+    }
+    let pos = origin
+        + u * select(0u, w, corner == 1u || corner == 2u)
+        + v * select(0u, h, corner >= 2u);
+
+    var ao = (quad.y >> (10u + corner * 2u)) & 3u;
+    if is_fluid == 1u {
+        // fluids lower the corners above their voxel
+        let voxel_y = origin.y - select(0u, 1u, normal_index == 3u);
+        ao = select(0u, (quad.y >> 20u) & 7u, pos.y > voxel_y);
+    }
+    return pos.x | (pos.y << 6u) | (pos.z << 12u) | (ao << 18u) | (normal_index << 21u)
+        | (is_fluid << 24u) | (block_index << 25u);
+}
+#endif
+
 fn x_positive_bits(bits: u32) -> u32{
     return (1u << bits) - 1u;
 }
 
 struct Vertex {
     @builtin(instance_index) instance_index: u32,
+#ifdef PACKED_QUADS
+    @builtin(vertex_index) vertex_index: u32,
+#else
     @location(0) vert_data: u32,
+#endif
     // @location(0) position: vec3<f32>,
     // @location(0) vert_data: u32,
     // @location(1) blend_color: vec4<f32>,
@@ -59,11 +107,17 @@ var<private> normals: array<vec3<f32>,6> = array<vec3<f32>,6> (
 fn vertex(vertex: Vertex) -> MyVertexOutput {
     var out: MyVertexOutput;
 
-    let x = f32((vertex.vert_data & 63u));
-    let y = f32((vertex.vert_data & 4032u) >> 6u);
-    let z = f32((vertex.vert_data & 258048u) >> 12u);
-    let ao = u32((vertex.vert_data & (3u << 18u)) >> 18u);
-    let normal_index = vertex.vert_data >> 21u & x_positive_bits(3u);
+#ifdef PACKED_QUADS
+    let vert_data = packed_quad_vertex(quads[vertex.vertex_index / 6u], vertex.vertex_index % 6u);
+#else
+    let vert_data = vertex.vert_data;
+#endif
+
+    let x = f32((vert_data & 63u));
+    let y = f32((vert_data & 4032u) >> 6u);
+    let z = f32((vert_data & 258048u) >> 12u);
+    let ao = u32((vert_data & (3u << 18u)) >> 18u);
+    let normal_index = vert_data >> 21u & x_positive_bits(3u);
 
 
     // let ambient_lerp = ambient_lerps[ao];
@@ -74,7 +128,11 @@ fn vertex(vertex: Vertex) -> MyVertexOutput {
     // out.world_normal = mesh_normal_local_to_world(normal, vertex.instance_index);
     out.world_normal = mesh_normal_local_to_world(normal, vertex.instance_index);
 
-    let local_position = vec4<f32>(x,y,z, 1.0);
+    var local_position = vec4<f32>(x,y,z, 1.0);
+    // fluid vertices are lowered by their level, see chunk.wgsl
+    if (vert_data >> 24u & 1u) == 1u {
+        local_position.y -= f32(vert_data >> 18u & 7u) / 8.0;
+    }
 
     // Use vertex_no_morph.instance_index instead of vertex.instance_index to work around a wgpu dx12 bug.
     // See https://github.com/gfx-rs/naga/issues/2416
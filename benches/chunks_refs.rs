@@ -34,7 +34,7 @@ fn iter_vec(data: Vec<BlockData>) {
         for z in 0..CHUNK_SIZE {
             for x in 0..CHUNK_SIZE {
                 let pos = ivec3(x as i32, y as i32, z as i32);
-                let index = vec3_to_index(pos, 32);
+                let index = vec3_to_index(pos, CHUNK_SIZE_I32);
                 let _b = black_box(data[index]);
             }
         }
@@ -59,9 +59,7 @@ fn criterion_benchmark(c: &mut Criterion) {
             || {
                 let mut d = vec![];
                 for _ in 0..CHUNK_SIZE_I32 * CHUNK_SIZE_I32 * CHUNK_SIZE_I32 {
-                    d.push(BlockData {
-                        block_type: BlockType::Air,
-                    });
+                    d.push(BlockData::from(BlockType::Air));
                 }
                 d
             },
@@ -1,24 +1,26 @@
 use std::{sync::Arc, time::Instant};
 
 use bevy::{math::IVec3, utils::HashMap};
-use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
 use new_voxel_testing::{
     chunk::ChunkData,
     chunks_refs::ChunksRefs,
-    culled_mesher, culled_mesher_optimized, greedy_mesher, greedy_mesher_optimized,
+    constants::{PlaneRow, CHUNK_SIZE, CHUNK_SIZE3},
+    culled_mesher, culled_mesher_optimized, greedy_mesher,
+    greedy_mesher_optimized::{self, PlaneBuckets},
     lod::Lod,
     utils::{index_to_ivec3, index_to_ivec3_bounds},
-    voxel::{BlockData, BlockType},
+    voxel::BlockType,
 };
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha8Rng;
 
 fn bench_mesh(chunks_refs: ChunksRefs) {
-    greedy_mesher::build_chunk_mesh(chunks_refs, Lod::L32);
+    greedy_mesher::build_chunk_mesh(&chunks_refs, Lod::L32);
 }
 
 fn bench_mesh_no_ao(chunks_refs: ChunksRefs) {
-    greedy_mesher::build_chunk_mesh_no_ao(chunks_refs, Lod::L32);
+    greedy_mesher::build_chunk_mesh_no_ao(&chunks_refs, Lod::L32);
 }
 
 fn binary_mesh_optimized(chunks_refs: ChunksRefs) {
@@ -38,11 +40,7 @@ fn make_chunks_refs(s: &mut u64) -> ChunksRefs {
 fn make_empty() -> ChunksRefs {
     let mut chunks = vec![];
     for _i in 0..3 * 3 * 3 {
-        chunks.push(Arc::new(ChunkData {
-            voxels: vec![BlockData {
-                block_type: BlockType::Air,
-            }],
-        }));
+        chunks.push(Arc::new(ChunkData::filled(BlockType::Air)));
     }
     ChunksRefs { chunks }
 }
@@ -50,17 +48,69 @@ fn make_empty() -> ChunksRefs {
 fn make_filled() -> ChunksRefs {
     let mut chunks = vec![];
     for _i in 0..3 * 3 * 3 {
-        chunks.push(Arc::new(ChunkData {
-            voxels: vec![BlockData {
-                block_type: BlockType::Grass,
-            }],
-        }));
+        chunks.push(Arc::new(ChunkData::filled(BlockType::Grass)));
     }
     ChunksRefs { chunks }
 }
 
-fn slicer(data: [u32; 32]) {
-    greedy_mesher_optimized::greedy_mesh_binary_plane(data, 32);
+fn slicer(data: [PlaneRow; CHUNK_SIZE]) {
+    greedy_mesher_optimized::greedy_mesh_binary_plane(data, CHUNK_SIZE as u32);
+}
+
+// (face, slice, block + ao, row, bit) of random faces using block_types distinct blocks
+fn make_faces(block_types: u32) -> Vec<(usize, u32, u32, usize, u32)> {
+    let mut rng = ChaCha8Rng::seed_from_u64(block_types as u64);
+    let mut faces = vec![];
+    for face in 0..6 {
+        for slice in 0..CHUNK_SIZE as u32 {
+            for row in 0..CHUNK_SIZE {
+                for bit in 0..CHUNK_SIZE as u32 {
+                    if rng.gen_range(0..16) == 0 {
+                        let block_ao = rng.gen_range(0..block_types) << 9;
+                        faces.push((face, slice, block_ao, row, bit));
+                    }
+                }
+            }
+        }
+    }
+    faces
+}
+
+// bucket faces into planes and greedy mesh them, returns the quad count
+fn bucket_planes(faces: &[(usize, u32, u32, usize, u32)]) -> usize {
+    let mut planes = PlaneBuckets::default();
+    for (face, slice, block_ao, row, bit) in faces.iter() {
+        planes.insert(*face, *slice, *block_ao, *row, *bit);
+    }
+    let mut quads = 0;
+    planes.for_each(|_face, _slice, _block_ao, plane| {
+        quads += greedy_mesher_optimized::greedy_mesh_binary_plane(plane, CHUNK_SIZE as u32).len();
+    });
+    quads
+}
+
+// the nested hashmap bucketing PlaneBuckets replaced, for comparison
+fn bucket_planes_hashmap(faces: &[(usize, u32, u32, usize, u32)]) -> usize {
+    let mut data: [HashMap<u32, HashMap<u32, [PlaneRow; CHUNK_SIZE]>>; 6] = Default::default();
+    for (face, slice, block_ao, row, bit) in faces.iter() {
+        let plane = data[*face]
+            .entry(*block_ao)
+            .or_default()
+            .entry(*slice)
+            .or_insert([0; CHUNK_SIZE]);
+        plane[*row] |= 1 << bit;
+    }
+    let mut quads = 0;
+    for block_ao_data in data.into_iter() {
+        for (_block_ao, axis_plane) in block_ao_data.into_iter() {
+            for (_slice, plane) in axis_plane.into_iter() {
+                quads +=
+                    greedy_mesher_optimized::greedy_mesh_binary_plane(plane, CHUNK_SIZE as u32)
+                        .len();
+            }
+        }
+    }
+    quads
 }
 
 fn criterion_benchmark(c: &mut Criterion) {
@@ -104,6 +154,53 @@ fn criterion_benchmark(c: &mut Criterion) {
     // });
 
     // let group = c.benchmark_group("yes");
+
+    // compare chunk sizes by running with the default, chunk_16 and chunk_62 features,
+    // throughput in Kelem/s is vertices (or voxels) per millisecond
+    let chunks: Vec<ChunksRefs> = (0..64).map(ChunksRefs::make_dummy_chunk_refs).collect();
+    let vertex_count: usize = chunks
+        .iter()
+        .filter_map(|c| greedy_mesher_optimized::build_chunk_mesh(c, Lod::L32))
+        .map(|m| m.vertices.len())
+        .sum();
+    let mut group = c.benchmark_group(format!("chunk size {CHUNK_SIZE}"));
+    group.throughput(Throughput::Elements(vertex_count as u64));
+    group.bench_function("GREEDY meshing OPTIMIZED: 64 chunks [vertices]", |b| {
+        b.iter(|| {
+            for chunks_refs in chunks.iter() {
+                black_box(greedy_mesher_optimized::build_chunk_mesh(
+                    chunks_refs,
+                    Lod::L32,
+                ));
+            }
+        })
+    });
+    group.throughput(Throughput::Elements((chunks.len() * CHUNK_SIZE3) as u64));
+    group.bench_function("GREEDY meshing OPTIMIZED: 64 chunks [voxels]", |b| {
+        b.iter(|| {
+            for chunks_refs in chunks.iter() {
+                black_box(greedy_mesher_optimized::build_chunk_mesh(
+                    chunks_refs,
+                    Lod::L32,
+                ));
+            }
+        })
+    });
+    group.finish();
+
+    // plane bucketing with many block types, faces are random so most planes hold few faces
+    let mut group = c.benchmark_group("plane bucketing");
+    for block_types in [2, 64, 1024] {
+        let faces = make_faces(block_types);
+        group.throughput(Throughput::Elements(faces.len() as u64));
+        group.bench_function(format!("PlaneBuckets: {block_types} block types"), |b| {
+            b.iter(|| black_box(bucket_planes(&faces)))
+        });
+        group.bench_function(format!("HashMap: {block_types} block types"), |b| {
+            b.iter(|| black_box(bucket_planes_hashmap(&faces)))
+        });
+    }
+    group.finish();
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use new_voxel_testing::{
+    constants::{PlaneRow, CHUNK_SIZE},
+    greedy_mesher_optimized::{check_plane_tiling, greedy_mesh_binary_plane},
+};
+
+fuzz_target!(|input: (u8, [PlaneRow; CHUNK_SIZE])| {
+    let (lod_size, data) = input;
+    let quads = greedy_mesh_binary_plane(data, lod_size as u32);
+    let rows = data.map(|row| row as u64);
+    if let Err(e) = check_plane_tiling(&rows, lod_size as u32, &quads) {
+        panic!("{e}");
+    }
+});
@@ -0,0 +1,14 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use new_voxel_testing::greedy_mesher_optimized::{
+    check_plane_tiling, greedy_mesh_binary_plane_u64,
+};
+
+fuzz_target!(|input: (u8, [u64; 64])| {
+    let (lod_size, data) = input;
+    let quads = greedy_mesh_binary_plane_u64(data, lod_size as u32);
+    if let Err(e) = check_plane_tiling(&data, lod_size as u32, &quads) {
+        panic!("{e}");
+    }
+});
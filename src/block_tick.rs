@@ -0,0 +1,128 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::{
+    voxel::BlockData,
+    voxel_engine::{get_world_block, start_modifications, VoxelEngine},
+};
+
+/// length of a single block tick
+pub const BLOCK_TICK_MS: u64 = 100;
+
+pub struct BlockTickPlugin;
+
+impl Plugin for BlockTickPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(BlockTickScheduler::default());
+        app.insert_resource(BlockTickTimer(Timer::new(
+            Duration::from_millis(BLOCK_TICK_MS),
+            TimerMode::Repeating,
+        )));
+        app.add_event::<BlockTick>();
+        app.configure_sets(
+            Update,
+            BlockTickSet
+                .after(dispatch_block_ticks)
+                .before(start_modifications),
+        );
+        app.add_systems(Update, dispatch_block_ticks);
+    }
+}
+
+/// handlers reading BlockTick events run in this set,
+/// so their modifications are applied in the same frame
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BlockTickSet;
+
+/// a scheduled tick came due for this voxel, block is its current content
+#[derive(Event, Debug, Copy, Clone)]
+pub struct BlockTick {
+    pub world_pos: IVec3,
+    pub block: BlockData,
+}
+
+// fixed rate the scheduler advances at
+#[derive(Resource)]
+pub struct BlockTickTimer(pub Timer);
+
+/// voxels waiting for a tick, keyed by the tick they are due on
+#[derive(Resource, Default)]
+pub struct BlockTickScheduler {
+    tick: u64,
+    scheduled: BTreeMap<u64, HashSet<IVec3>>,
+}
+
+impl BlockTickScheduler {
+    pub fn current_tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// tick the voxel at world_pos after delay ticks, a delay of 0 is treated as 1
+    pub fn schedule(&mut self, world_pos: IVec3, delay: u64) {
+        self.scheduled
+            .entry(self.tick + delay.max(1))
+            .or_default()
+            .insert(world_pos);
+    }
+
+    pub fn is_scheduled(&self, world_pos: IVec3) -> bool {
+        self.scheduled.values().any(|set| set.contains(&world_pos))
+    }
+
+    /// move on to the next tick, returning every voxel due on it
+    pub fn advance(&mut self) -> HashSet<IVec3> {
+        self.tick += 1;
+        self.scheduled.remove(&self.tick).unwrap_or_default()
+    }
+}
+
+/// at most one tick per frame, so every tick sees the modifications of the previous one
+pub fn dispatch_block_ticks(
+    time: Res<Time>,
+    mut timer: ResMut<BlockTickTimer>,
+    mut scheduler: ResMut<BlockTickScheduler>,
+    voxel_engine: Res<VoxelEngine>,
+    mut ticks: EventWriter<BlockTick>,
+) {
+    timer.0.tick(time.delta());
+    if !timer.0.just_finished() {
+        return;
+    }
+    for world_pos in scheduler.advance() {
+        // ticks in unloaded chunks are dropped
+        let Some(block) = get_world_block(&voxel_engine.world_data, world_pos) else {
+            continue;
+        };
+        ticks.send(BlockTick {
+            world_pos,
+            block: *block,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticks_come_due_in_order() {
+        let mut scheduler = BlockTickScheduler::default();
+        scheduler.schedule(IVec3::X, 2);
+        scheduler.schedule(IVec3::Y, 1);
+        scheduler.schedule(IVec3::Y, 1);
+        scheduler.schedule(IVec3::Z, 0);
+        assert!(scheduler.is_scheduled(IVec3::X));
+
+        let first = scheduler.advance();
+        assert_eq!(first.len(), 2);
+        assert!(first.contains(&IVec3::Y) && first.contains(&IVec3::Z));
+        assert_eq!(
+            scheduler.advance().into_iter().collect::<Vec<_>>(),
+            [IVec3::X]
+        );
+        assert!(scheduler.advance().is_empty());
+        assert_eq!(scheduler.current_tick(), 3);
+        assert!(!scheduler.is_scheduled(IVec3::X));
+    }
+}
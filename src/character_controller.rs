@@ -0,0 +1,96 @@
+use bevy::prelude::*;
+
+use crate::{
+    collision::{move_and_collide, CollisionBox},
+    voxel_engine::VoxelEngine,
+};
+
+pub struct CharacterControllerPlugin;
+
+impl Plugin for CharacterControllerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, move_characters);
+    }
+}
+
+/// walks the entity through the voxel world, colliding against solid voxels
+/// the transform is treated as the eye position, the collider hangs below it
+#[derive(Component)]
+pub struct CharacterController {
+    pub half_extents: Vec3,
+    /// distance from the collider center up to the transform
+    pub eye_offset: f32,
+    /// highest ledge that is walked onto without jumping
+    pub step_height: f32,
+    pub walk_speed: f32,
+    pub jump_speed: f32,
+    pub gravity: f32,
+    pub max_fall_speed: f32,
+    pub velocity: Vec3,
+    pub grounded: bool,
+}
+
+impl Default for CharacterController {
+    fn default() -> Self {
+        Self {
+            half_extents: Vec3::new(0.3, 0.9, 0.3),
+            eye_offset: 0.7,
+            step_height: 1.05,
+            walk_speed: 6.0,
+            jump_speed: 8.0,
+            gravity: 28.0,
+            max_fall_speed: 60.0,
+            velocity: Vec3::ZERO,
+            grounded: false,
+        }
+    }
+}
+
+fn move_characters(
+    time: Res<Time>,
+    keys: Res<ButtonInput<KeyCode>>,
+    voxel_engine: Res<VoxelEngine>,
+    mut characters: Query<(&mut Transform, &mut CharacterController)>,
+) {
+    let dt = time.delta_seconds();
+    for (mut transform, mut controller) in characters.iter_mut() {
+        // walk on the horizontal plane, regardless of where we look
+        let forward = (*transform.forward() * Vec3::new(1.0, 0.0, 1.0)).normalize_or_zero();
+        let right = (*transform.right() * Vec3::new(1.0, 0.0, 1.0)).normalize_or_zero();
+        let mut wish_dir = Vec3::ZERO;
+        if keys.pressed(KeyCode::KeyW) {
+            wish_dir += forward;
+        }
+        if keys.pressed(KeyCode::KeyS) {
+            wish_dir -= forward;
+        }
+        if keys.pressed(KeyCode::KeyD) {
+            wish_dir += right;
+        }
+        if keys.pressed(KeyCode::KeyA) {
+            wish_dir -= right;
+        }
+        let horizontal = wish_dir.normalize_or_zero() * controller.walk_speed;
+        controller.velocity.x = horizontal.x;
+        controller.velocity.z = horizontal.z;
+
+        if controller.grounded && keys.just_pressed(KeyCode::Space) {
+            controller.velocity.y = controller.jump_speed;
+        }
+        controller.velocity.y =
+            (controller.velocity.y - controller.gravity * dt).max(-controller.max_fall_speed);
+
+        let center = transform.translation - Vec3::Y * controller.eye_offset;
+        let result = move_and_collide(
+            &voxel_engine.world_data,
+            CollisionBox::from_center(center, controller.half_extents),
+            controller.velocity * dt,
+            controller.step_height,
+        );
+        transform.translation += result.translation;
+        if result.blocked.y {
+            controller.velocity.y = 0.0;
+        }
+        controller.grounded = result.grounded;
+    }
+}
@@ -2,16 +2,120 @@ use bevy::prelude::*;
 use bracket_noise::prelude::*;
 
 use crate::{
+    constants::{PlaneRow, CHUNK_SIZE, CHUNK_SIZE3, CHUNK_SIZE_I32},
     utils::index_to_ivec3,
     voxel::{BlockData, BlockType},
 };
 
 #[derive(Clone)]
 pub struct ChunkData {
-    pub voxels: Vec<BlockData>,
+    // only written by new and set_block, so the occupancy masks stay in sync
+    voxels: Vec<BlockData>,
+    // None for filled chunks, their single voxel says it all
+    occupancy: Option<Box<ChunkOccupancy>>,
+}
+
+/// solid voxels of a chunk as bit columns along every axis
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChunkOccupancy {
+    /// [z][x] columns along y, [y][z] columns along x and [y][x] columns along z
+    pub cols: [[[PlaneRow; CHUNK_SIZE]; CHUNK_SIZE]; 3],
+}
+
+impl ChunkOccupancy {
+    pub fn from_voxels(voxels: &[BlockData]) -> Self {
+        let mut occupancy = Self {
+            cols: [[[0; CHUNK_SIZE]; CHUNK_SIZE]; 3],
+        };
+        for (i, voxel) in voxels.iter().enumerate() {
+            if voxel.block_type.is_solid() {
+                occupancy.set(index_to_ivec3(i as i32), true);
+            }
+        }
+        occupancy
+    }
+
+    #[inline]
+    pub fn set(&mut self, pos: IVec3, solid: bool) {
+        let [x, y, z] = pos.to_array().map(|p| p as usize);
+        for (axis, a, b, bit) in [(0, z, x, y), (1, y, z, x), (2, y, x, z)] {
+            let col = &mut self.cols[axis][a][b];
+            match solid {
+                true => *col |= 1 << bit,
+                false => *col &= !(1 << bit),
+            }
+        }
+    }
+
+    #[inline]
+    pub fn is_solid(&self, pos: IVec3) -> bool {
+        self.cols[0][pos.z as usize][pos.x as usize] >> pos.y & 1 == 1
+    }
 }
 
 impl ChunkData {
+    pub fn new(voxels: Vec<BlockData>) -> Self {
+        let occupancy = match voxels.len() {
+            1 => None,
+            _ => Some(Box::new(ChunkOccupancy::from_voxels(&voxels))),
+        };
+        Self { voxels, occupancy }
+    }
+
+    /// chunk where every voxel is the same block, stored as a single voxel
+    pub fn filled(block_type: BlockType) -> Self {
+        Self::new(vec![BlockData::from(block_type)])
+    }
+
+    /// every voxel of the chunk, or a single one if the chunk is filled
+    #[inline]
+    pub fn voxels(&self) -> &[BlockData] {
+        &self.voxels
+    }
+
+    /// cached solid masks, None if the chunk is filled with a single block
+    #[inline]
+    pub fn occupancy(&self) -> Option<&ChunkOccupancy> {
+        self.occupancy.as_deref()
+    }
+
+    /// solid column of the occupancy masks, see ChunkOccupancy::cols
+    #[inline]
+    pub fn solid_column(&self, axis: usize, a: usize, b: usize) -> PlaneRow {
+        match self.occupancy() {
+            Some(occupancy) => occupancy.cols[axis][a][b],
+            None if self.voxels[0].block_type.is_solid() => {
+                PlaneRow::MAX >> (PlaneRow::BITS as usize - CHUNK_SIZE)
+            }
+            None => 0,
+        }
+    }
+
+    /// solidity of the voxel at a local position, read from the occupancy masks
+    #[inline]
+    pub fn is_solid(&self, pos: IVec3) -> bool {
+        match self.occupancy() {
+            Some(occupancy) => occupancy.is_solid(pos),
+            None => self.voxels[0].block_type.is_solid(),
+        }
+    }
+
+    /// replace the voxel at index, expanding filled chunks. returns the previous voxel
+    pub fn set_block(&mut self, index: usize, block: BlockData) -> BlockData {
+        if self.voxels.len() == 1 {
+            self.voxels = vec![self.voxels[0]; CHUNK_SIZE3];
+            self.occupancy = Some(Box::new(ChunkOccupancy::from_voxels(&self.voxels)));
+        }
+        let previous = std::mem::replace(&mut self.voxels[index], block);
+        let solid = block.block_type.is_solid();
+        if let Some(occupancy) = self.occupancy.as_mut() {
+            if previous.block_type.is_solid() != solid {
+                occupancy.set(index_to_ivec3(index as i32), solid);
+            }
+        }
+        previous
+    }
+
     #[inline]
     pub fn get_block(&self, index: usize) -> &BlockData {
         if self.voxels.len() == 1 {
@@ -34,26 +138,18 @@ impl ChunkData {
     ///! shape our voxel data based on the chunk_pos
     pub fn generate(chunk_pos: IVec3) -> Self {
         // hardcoded extremity check
-        if chunk_pos.y * 32 + 32 > 21 + 32 {
-            return Self {
-                voxels: vec![BlockData {
-                    block_type: BlockType::Air,
-                }],
-            };
+        if chunk_pos.y * CHUNK_SIZE_I32 + CHUNK_SIZE_I32 > 21 + CHUNK_SIZE_I32 {
+            return Self::filled(BlockType::Air);
         }
         // hardcoded extremity check
-        if chunk_pos.y * 32 < -21 - 32 {
-            return Self {
-                voxels: vec![BlockData {
-                    block_type: BlockType::Grass,
-                }],
-            };
+        if chunk_pos.y * CHUNK_SIZE_I32 < -21 - CHUNK_SIZE_I32 {
+            return Self::filled(BlockType::Grass);
         }
         let mut voxels = vec![];
         let mut fast_noise = FastNoise::new();
         fast_noise.set_frequency(0.0254);
-        for i in 0..32 * 32 * 32 {
-            let voxel_pos = (chunk_pos * 32) + index_to_ivec3(i);
+        for i in 0..CHUNK_SIZE3 as i32 {
+            let voxel_pos = (chunk_pos * CHUNK_SIZE_I32) + index_to_ivec3(i);
             let scale = 1.0;
             fast_noise.set_frequency(0.0254);
             let overhang = fast_noise.get_noise3d(
@@ -74,9 +170,61 @@ impl ChunkData {
                 },
                 false => BlockType::Air,
             };
-            voxels.push(BlockData { block_type });
+            voxels.push(BlockData::from(block_type));
         }
 
-        Self { voxels }
+        Self::new(voxels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::vec3_to_index;
+
+    #[test]
+    fn set_block_keeps_occupancy_in_sync() {
+        let mut chunk = ChunkData::filled(BlockType::Dirt);
+        assert!(chunk.occupancy().is_none());
+        assert!(chunk.is_solid(IVec3::new(1, 2, 3)));
+        let full = chunk.solid_column(0, 0, 0);
+        assert_eq!(full.count_ones() as usize, CHUNK_SIZE);
+
+        let edits = [
+            (IVec3::new(0, 0, 0), BlockType::Air),
+            (IVec3::new(3, CHUNK_SIZE_I32 - 1, 5), BlockType::Water),
+            (IVec3::new(3, CHUNK_SIZE_I32 - 1, 5), BlockType::Sand),
+            (IVec3::new(7, 2, 1), BlockType::Air),
+            (IVec3::new(7, 2, 1), BlockType::Grass),
+            (IVec3::new(CHUNK_SIZE_I32 - 1, 9, 4), BlockType::Lava),
+        ];
+        for (pos, block_type) in edits {
+            let i = vec3_to_index(pos, CHUNK_SIZE_I32);
+            chunk.set_block(i, BlockData::from(block_type));
+            let occupancy = chunk.occupancy().unwrap();
+            assert_eq!(*occupancy, ChunkOccupancy::from_voxels(&chunk.voxels));
+            assert_eq!(occupancy.is_solid(pos), block_type.is_solid());
+            assert_eq!(chunk.is_solid(pos), block_type.is_solid());
+        }
+        assert_eq!(chunk.solid_column(1, 9, 4), full & !(1 << (CHUNK_SIZE - 1)));
+    }
+
+    #[test]
+    fn generated_occupancy_matches_voxels() {
+        let chunk = ChunkData::generate(IVec3::new(2, 0, -3));
+        let occupancy = chunk.occupancy().expect("generated chunks are not filled");
+        for (i, voxel) in chunk.voxels.iter().enumerate() {
+            let pos = index_to_ivec3(i as i32);
+            assert_eq!(occupancy.is_solid(pos), voxel.block_type.is_solid());
+            let [x, y, z] = pos.to_array().map(|p| p as usize);
+            assert_eq!(
+                occupancy.cols[1][y][z] >> x & 1 == 1,
+                voxel.block_type.is_solid()
+            );
+            assert_eq!(
+                occupancy.cols[2][y][x] >> z & 1 == 1,
+                voxel.block_type.is_solid()
+            );
+        }
     }
 }
@@ -1,6 +1,37 @@
+use bevy::prelude::*;
+
+use crate::voxel::MESHABLE_BLOCK_TYPES;
+
 ///! gpu ready mesh payload
 #[derive(Default)]
 pub struct ChunkMesh {
     pub indices: Vec<u32>,
     pub vertices: Vec<u32>,
+    /// one packed u64 per quad instead of vertices and indices,
+    /// see greedy_mesher_optimized::build_chunk_quads and packed_quad
+    pub quads: Vec<u64>,
+    /// only built when requested, see greedy_mesher_optimized::build_chunk_mesh_with_collision
+    pub collision: Option<ChunkCollisionMesh>,
+    /// triangle mesh of the solid blocks, see smooth_mesher. fluids stay in vertices and indices
+    pub smooth: Option<SmoothMesh>,
+}
+
+/// weight of each MESHABLE_BLOCK_TYPES entry, summing to 1
+pub type MaterialWeights = [f32; MESHABLE_BLOCK_TYPES.len()];
+
+/// smooth surface with per vertex normals and material weights, positions are in chunk space
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct SmoothMesh {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub weights: Vec<MaterialWeights>,
+    pub indices: Vec<u32>,
+}
+
+/// simplified collision geometry for physics engines
+/// positions are in chunk space, quads are merged across block types and ignore ambient occlusion
+#[derive(Component, Default, Clone, Debug)]
+pub struct ChunkCollisionMesh {
+    pub positions: Vec<[f32; 3]>,
+    pub indices: Vec<u32>,
 }
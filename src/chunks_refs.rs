@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{fmt, sync::Arc};
 
 use bevy::{
     math::{ivec3, IVec3},
@@ -9,6 +9,7 @@ use rand_chacha::ChaCha8Rng;
 
 use crate::{
     chunk::ChunkData,
+    constants::CHUNK_SIZE_I32,
     quad::Direction,
     utils::{index_to_ivec3_bounds, vec3_to_index},
     voxel::BlockData,
@@ -20,21 +21,38 @@ pub struct ChunksRefs {
     pub chunks: Vec<Arc<ChunkData>>,
 }
 
+/// chunk positions that weren't loaded when building a ChunksRefs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingChunks(pub Vec<IVec3>);
+
+impl fmt::Display for MissingChunks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "missing chunk data for {:?}", self.0)
+    }
+}
+
+impl std::error::Error for MissingChunks {}
+
 impl ChunksRefs {
     ///! construct a ChunkRefs at middle_chunk position
-    ///! safety: panics if ChunkData doesn't exist in input world_data
+    ///! fails with every position (middle included) that has no ChunkData in world_data
     pub fn try_new(
         world_data: &HashMap<IVec3, Arc<ChunkData>>,
         middle_chunk: IVec3,
-    ) -> Option<Self> {
+    ) -> Result<Self, MissingChunks> {
         let mut chunks = vec![];
+        let mut missing = vec![];
         for i in 0..3 * 3 * 3 {
             let offset = index_to_ivec3_bounds(i, 3) + IVec3::splat(-1);
-            chunks.push(Arc::clone(
-                world_data.get(&(middle_chunk + offset)).unwrap(),
-            ))
+            match world_data.get(&(middle_chunk + offset)) {
+                Some(chunk) => chunks.push(Arc::clone(chunk)),
+                None => missing.push(middle_chunk + offset),
+            }
+        }
+        match missing.is_empty() {
+            true => Ok(Self { chunks }),
+            false => Err(MissingChunks(missing)),
         }
-        Some(Self { chunks })
     }
     // returns if all the voxels are the same
     // this is an incredibly fast approximation (1 sample per chunk) all = voxels[0]
@@ -76,16 +94,17 @@ impl ChunksRefs {
     ///! helper function to get block data that may exceed the bounds of the middle chunk
     ///! input position is local pos to middle chunk
     pub fn get_block(&self, pos: IVec3) -> &BlockData {
-        let x = (pos.x + 32) as u32;
-        let y = (pos.y + 32) as u32;
-        let z = (pos.z + 32) as u32;
-        let (x_chunk, x) = ((x / 32) as i32, (x % 32) as i32);
-        let (y_chunk, y) = ((y / 32) as i32, (y % 32) as i32);
-        let (z_chunk, z) = ((z / 32) as i32, (z % 32) as i32);
+        let size = CHUNK_SIZE_I32 as u32;
+        let x = (pos.x + CHUNK_SIZE_I32) as u32;
+        let y = (pos.y + CHUNK_SIZE_I32) as u32;
+        let z = (pos.z + CHUNK_SIZE_I32) as u32;
+        let (x_chunk, x) = ((x / size) as i32, (x % size) as i32);
+        let (y_chunk, y) = ((y / size) as i32, (y % size) as i32);
+        let (z_chunk, z) = ((z / size) as i32, (z % size) as i32);
 
         let chunk_index = vec3_to_index(IVec3::new(x_chunk, y_chunk, z_chunk), 3);
         let chunk_data = &self.chunks[chunk_index];
-        let i = vec3_to_index(IVec3::new(x, y, z), 32);
+        let i = vec3_to_index(IVec3::new(x, y, z), CHUNK_SIZE_I32);
         chunk_data.get_block(i)
     }
 
@@ -93,7 +112,7 @@ impl ChunksRefs {
     ///! panics if the local pos is outside the middle chunk
     pub fn get_block_no_neighbour(&self, pos: IVec3) -> &BlockData {
         let chunk_data = &self.chunks[13];
-        let i = vec3_to_index(pos, 32);
+        let i = vec3_to_index(pos, CHUNK_SIZE_I32);
         chunk_data.get_block(i)
     }
 
@@ -128,3 +147,59 @@ impl ChunksRefs {
         (first, second)
     }
 }
+
+#[cfg(test)]
+impl ChunksRefs {
+    /// every chunk filled with a single block
+    pub fn uniform(block_type: crate::voxel::BlockType) -> ChunksRefs {
+        let chunks = (0..3 * 3 * 3)
+            .map(|_| Arc::new(ChunkData::filled(block_type)))
+            .collect();
+        ChunksRefs { chunks }
+    }
+
+    /// the middle chunk surrounded by air
+    pub fn with_middle(middle: ChunkData) -> ChunksRefs {
+        let mut chunks_refs = Self::uniform(crate::voxel::BlockType::Air);
+        chunks_refs.chunks[13] = Arc::new(middle);
+        chunks_refs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::BlockType;
+
+    fn filled_world(center: IVec3) -> HashMap<IVec3, Arc<ChunkData>> {
+        let mut world_data = HashMap::new();
+        for i in 0..3 * 3 * 3 {
+            let pos = center + index_to_ivec3_bounds(i, 3) + IVec3::NEG_ONE;
+            world_data.insert(pos, Arc::new(ChunkData::filled(BlockType::Dirt)));
+        }
+        world_data
+    }
+
+    #[test]
+    fn try_new_names_missing_neighbours() {
+        let center = IVec3::new(4, -2, 7);
+        let mut world_data = filled_world(center);
+        assert!(ChunksRefs::try_new(&world_data, center).is_ok());
+
+        world_data.remove(&(center + IVec3::new(1, 1, 1)));
+        world_data.remove(&(center + IVec3::NEG_Y));
+        let Err(MissingChunks(missing)) = ChunksRefs::try_new(&world_data, center) else {
+            panic!("expected missing chunks");
+        };
+        assert_eq!(
+            missing,
+            [center + IVec3::NEG_Y, center + IVec3::new(1, 1, 1)]
+        );
+        // nothing loaded at all, every position is reported
+        let far = center + IVec3::X * 10;
+        assert_eq!(
+            ChunksRefs::try_new(&world_data, far).err().unwrap().0.len(),
+            27
+        );
+    }
+}
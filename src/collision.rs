@@ -0,0 +1,315 @@
+use std::sync::Arc;
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{chunk::ChunkData, voxel_engine::is_world_voxel_solid};
+
+/// gap kept between a box and the voxel it was stopped by,
+/// so the next move doesn't start out overlapping it
+pub const COLLISION_SKIN: f32 = 0.001;
+
+/// axis aligned box in world space
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CollisionBox {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl CollisionBox {
+    pub fn from_center(center: Vec3, half_extents: Vec3) -> Self {
+        Self {
+            min: center - half_extents,
+            max: center + half_extents,
+        }
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn translated(&self, offset: Vec3) -> Self {
+        Self {
+            min: self.min + offset,
+            max: self.max + offset,
+        }
+    }
+
+    /// inclusive range of voxels overlapped by the box
+    /// touching a voxel face is not considered overlapping
+    pub fn voxel_range(&self) -> (IVec3, IVec3) {
+        (
+            self.min.floor().as_ivec3(),
+            self.max.ceil().as_ivec3() - IVec3::ONE,
+        )
+    }
+}
+
+/// result of moving a box through the voxel world
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MoveResult {
+    /// translation that was applied after resolving collisions
+    pub translation: Vec3,
+    /// true for every axis where the movement was cut short
+    pub blocked: BVec3,
+    /// resting on a solid voxel after the move
+    pub grounded: bool,
+}
+
+/// voxels in unloaded chunks count as solid,
+/// so nothing falls out of the world while chunk data is still generating
+#[inline]
+pub fn is_voxel_solid(world_data: &HashMap<IVec3, Arc<ChunkData>>, world_pos: IVec3) -> bool {
+    is_world_voxel_solid(world_data, world_pos).unwrap_or(true)
+}
+
+/// true if any voxel overlapped by the box is solid
+pub fn overlaps_solid(world_data: &HashMap<IVec3, Arc<ChunkData>>, bbox: &CollisionBox) -> bool {
+    let (min, max) = bbox.voxel_range();
+    for z in min.z..=max.z {
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                if is_voxel_solid(world_data, IVec3::new(x, y, z)) {
+                    return true;
+                }
+            }
+        }
+    }
+    false
+}
+
+/// true if there is solid ground right below the box
+pub fn is_grounded(world_data: &HashMap<IVec3, Arc<ChunkData>>, bbox: &CollisionBox) -> bool {
+    let probe = CollisionBox {
+        min: bbox.min - Vec3::Y * COLLISION_SKIN * 2.0,
+        max: Vec3::new(bbox.max.x, bbox.min.y, bbox.max.z),
+    };
+    overlaps_solid(world_data, &probe)
+}
+
+/// true if a layer of voxels perpendicular to axis contains a solid voxel
+/// the layer spans the other two axes of the voxel range
+fn layer_has_solid(
+    world_data: &HashMap<IVec3, Arc<ChunkData>>,
+    axis: usize,
+    layer: i32,
+    min: IVec3,
+    max: IVec3,
+) -> bool {
+    let (a, b) = match axis {
+        0 => (1, 2),
+        1 => (0, 2),
+        _ => (0, 1),
+    };
+    for i in min[a]..=max[a] {
+        for j in min[b]..=max[b] {
+            let mut pos = IVec3::ZERO;
+            pos[axis] = layer;
+            pos[a] = i;
+            pos[b] = j;
+            if is_voxel_solid(world_data, pos) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// sweep the box along a single axis (0 = x, 1 = y, 2 = z)
+/// every voxel layer between start and end is visited, so large deltas can't tunnel
+/// returns the distance that could be travelled before hitting a solid voxel
+pub fn sweep_axis(
+    world_data: &HashMap<IVec3, Arc<ChunkData>>,
+    bbox: &CollisionBox,
+    axis: usize,
+    delta: f32,
+) -> f32 {
+    let (min, max) = bbox.voxel_range();
+    if delta > 0.0 {
+        // leading face is max, entering layers above it
+        let start = bbox.max[axis];
+        let first = start.ceil() as i32;
+        let last = (start + delta).ceil() as i32 - 1;
+        for layer in first..=last {
+            if layer_has_solid(world_data, axis, layer, min, max) {
+                return (layer as f32 - COLLISION_SKIN - start).clamp(0.0, delta);
+            }
+        }
+    } else if delta < 0.0 {
+        // leading face is min, entering layers below it
+        let start = bbox.min[axis];
+        let first = start.floor() as i32 - 1;
+        let last = (start + delta).floor() as i32;
+        for layer in (last..=first).rev() {
+            if layer_has_solid(world_data, axis, layer, min, max) {
+                return (layer as f32 + 1.0 + COLLISION_SKIN - start).clamp(delta, 0.0);
+            }
+        }
+    }
+    delta
+}
+
+/// move a box through the world, resolving collisions one axis at a time (y, x, z)
+/// a blocked horizontal move on the ground is retried up to step_height higher
+pub fn move_and_collide(
+    world_data: &HashMap<IVec3, Arc<ChunkData>>,
+    bbox: CollisionBox,
+    delta: Vec3,
+    step_height: f32,
+) -> MoveResult {
+    let mut bbox = bbox;
+    let start = bbox.center();
+    let mut blocked = [false; 3];
+
+    let dy = sweep_axis(world_data, &bbox, 1, delta.y);
+    blocked[1] = dy != delta.y;
+    bbox = bbox.translated(Vec3::Y * dy);
+
+    let can_step = step_height > 0.0 && is_grounded(world_data, &bbox);
+    for axis in [0, 2] {
+        let moved = sweep_axis(world_data, &bbox, axis, delta[axis]);
+        let mut offset = Vec3::ZERO;
+        offset[axis] = moved;
+        if moved == delta[axis] || !can_step {
+            blocked[axis] = moved != delta[axis];
+            bbox = bbox.translated(offset);
+            continue;
+        }
+
+        // try stepping up the obstacle
+        let up = sweep_axis(world_data, &bbox, 1, step_height);
+        let raised = bbox.translated(Vec3::Y * up);
+        let stepped = sweep_axis(world_data, &raised, axis, delta[axis]);
+        if stepped.abs() > moved.abs() {
+            let mut step_offset = Vec3::Y * up;
+            step_offset[axis] = stepped;
+            let raised = bbox.translated(step_offset);
+            // settle back down on top of the obstacle
+            let down = sweep_axis(world_data, &raised, 1, -up);
+            bbox = raised.translated(Vec3::Y * down);
+            blocked[axis] = stepped != delta[axis];
+        } else {
+            bbox = bbox.translated(offset);
+            blocked[axis] = true;
+        }
+    }
+
+    MoveResult {
+        translation: bbox.center() - start,
+        blocked: BVec3::new(blocked[0], blocked[1], blocked[2]),
+        grounded: is_grounded(world_data, &bbox),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        constants::CHUNK_SIZE_I32,
+        test_utils::{chunk_from_fn, flat_world, set_block},
+        voxel::{BlockData, BlockType},
+    };
+
+    const HALF_EXTENTS: Vec3 = Vec3::new(0.3, 0.9, 0.3);
+
+    // place dirt at world voxel positions, inside loaded chunks
+    fn set_blocks(world_data: &mut HashMap<IVec3, Arc<ChunkData>>, positions: &[IVec3]) {
+        for pos in positions {
+            set_block(world_data, *pos, BlockType::Dirt.into());
+        }
+    }
+
+    fn standing_box(x: f32, z: f32) -> CollisionBox {
+        CollisionBox::from_center(
+            Vec3::new(x, HALF_EXTENTS.y + COLLISION_SKIN, z),
+            HALF_EXTENTS,
+        )
+    }
+
+    #[test]
+    fn falls_and_lands_on_floor() {
+        let world_data = flat_world(-1..=1);
+        let bbox = CollisionBox::from_center(Vec3::new(16.5, 5.0, 16.5), HALF_EXTENTS);
+        let result = move_and_collide(&world_data, bbox, Vec3::NEG_Y * 10.0, 0.0);
+        let landed = bbox.translated(result.translation);
+        assert!(result.blocked.y);
+        assert!(result.grounded);
+        assert!((landed.min.y - COLLISION_SKIN).abs() < 1e-4);
+    }
+
+    #[test]
+    fn large_delta_does_not_tunnel() {
+        let mut world_data = flat_world(-1..=1);
+        // a floor across chunk (0,0,0)
+        let floor = chunk_from_fn(|pos| match pos.y == 4 {
+            true => BlockType::Dirt.into(),
+            false => BlockData::default(),
+        });
+        world_data.insert(IVec3::ZERO, Arc::new(floor));
+        let bbox = CollisionBox::from_center(Vec3::new(8.5, 20.0, 8.5), HALF_EXTENTS);
+        let result = move_and_collide(&world_data, bbox, Vec3::NEG_Y * 100.0, 0.0);
+        let landed = bbox.translated(result.translation);
+        assert!(result.grounded);
+        assert!((landed.min.y - (5.0 + COLLISION_SKIN)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn wall_blocks_movement() {
+        let mut world_data = flat_world(-1..=1);
+        set_blocks(
+            &mut world_data,
+            &[IVec3::new(20, 0, 16), IVec3::new(20, 1, 16)],
+        );
+        let bbox = standing_box(18.5, 16.5);
+        let result = move_and_collide(&world_data, bbox, Vec3::X * 5.0, 1.1);
+        let moved = bbox.translated(result.translation);
+        assert!(result.blocked.x);
+        assert!(!result.blocked.z);
+        assert!((moved.max.x - (20.0 - COLLISION_SKIN)).abs() < 1e-4);
+        assert!((moved.min.y - bbox.min.y).abs() < 1e-4);
+    }
+
+    #[test]
+    fn steps_up_single_block() {
+        let mut world_data = flat_world(-1..=1);
+        let platform: Vec<IVec3> = (20..24).map(|x| IVec3::new(x, 0, 16)).collect();
+        set_blocks(&mut world_data, &platform);
+        let bbox = standing_box(18.5, 16.5);
+        let result = move_and_collide(&world_data, bbox, Vec3::X * 3.0, 1.1);
+        let moved = bbox.translated(result.translation);
+        assert!(!result.blocked.x);
+        assert!(result.grounded);
+        assert!((moved.center().x - 21.5).abs() < 1e-4);
+        assert!((moved.min.y - (1.0 + COLLISION_SKIN)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn no_step_without_step_height() {
+        let mut world_data = flat_world(-1..=1);
+        set_blocks(&mut world_data, &[IVec3::new(20, 0, 16)]);
+        let bbox = standing_box(18.5, 16.5);
+        let result = move_and_collide(&world_data, bbox, Vec3::X * 3.0, 0.0);
+        assert!(result.blocked.x);
+        assert!(result.translation.y.abs() < 1e-4);
+    }
+
+    #[test]
+    fn slides_along_wall() {
+        let mut world_data = flat_world(-1..=1);
+        let wall: Vec<IVec3> = (0..CHUNK_SIZE_I32).map(|z| IVec3::new(20, 0, z)).collect();
+        set_blocks(&mut world_data, &wall);
+        let bbox = standing_box(18.5, 10.5);
+        let result = move_and_collide(&world_data, bbox, Vec3::new(3.0, 0.0, 2.0), 0.0);
+        assert!(result.blocked.x);
+        assert!(!result.blocked.z);
+        assert!((result.translation.z - 2.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn unloaded_chunks_are_solid() {
+        let world_data = HashMap::new();
+        let bbox = CollisionBox::from_center(Vec3::new(16.5, 16.5, 16.5), HALF_EXTENTS);
+        let result = move_and_collide(&world_data, bbox, Vec3::new(2.0, -2.0, 2.0), 0.0);
+        assert!(result.blocked.all());
+        assert!(result.grounded);
+    }
+}
@@ -3,7 +3,17 @@ use bevy::{
     prelude::IVec3,
 };
 
+#[cfg(all(feature = "chunk_16", feature = "chunk_62"))]
+compile_error!("features chunk_16 and chunk_62 are mutually exclusive");
+
+// chunk dimension, with padding the voxel columns must fit in a u64
+#[cfg(feature = "chunk_16")]
+pub const CHUNK_SIZE: usize = 16;
+#[cfg(feature = "chunk_62")]
+pub const CHUNK_SIZE: usize = 62;
+#[cfg(not(any(feature = "chunk_16", feature = "chunk_62")))]
 pub const CHUNK_SIZE: usize = 32;
+
 pub const CHUNK_SIZE_I32: i32 = CHUNK_SIZE as i32;
 pub const CHUNK_SIZE_P: usize = CHUNK_SIZE + 2;
 pub const CHUNK_SIZE_P2: usize = CHUNK_SIZE_P * CHUNK_SIZE_P;
@@ -12,6 +22,15 @@ pub const CHUNK_SIZE2: usize = CHUNK_SIZE * CHUNK_SIZE;
 pub const CHUNK_SIZE2_I32: i32 = CHUNK_SIZE2 as i32;
 pub const CHUNK_SIZE3: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
 
+/// one row of a binary plane, a bit for every voxel along a chunk axis
+#[cfg(not(feature = "chunk_62"))]
+pub type PlaneRow = u32;
+#[cfg(feature = "chunk_62")]
+pub type PlaneRow = u64;
+
+const _: () = assert!(CHUNK_SIZE_P <= u64::BITS as usize);
+const _: () = assert!(CHUNK_SIZE <= PlaneRow::BITS as usize);
+
 pub const ADJACENT_CHUNK_DIRECTIONS: [IVec3; 27] = [
     IVec3 { x: 0, y: 0, z: 0 },
     // moore neighbours in the negative direction
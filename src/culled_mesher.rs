@@ -3,6 +3,8 @@ use bevy::{math::ivec3, prelude::*};
 use crate::{
     chunk_mesh::ChunkMesh,
     chunks_refs::ChunksRefs,
+    constants::CHUNK_SIZE3,
+    fluid::append_fluid_vertices,
     lod::Lod,
     quad::{Direction, Quad},
     utils::{generate_indices, index_to_ivec3, make_vertex_u32},
@@ -20,9 +22,9 @@ fn push_face(mesh: &mut ChunkMesh, dir: Direction, vpos: IVec3, color: Color, bl
     }
 }
 
-pub fn build_chunk_mesh_no_ao(chunks_refs: ChunksRefs, _lod: Lod) -> Option<ChunkMesh> {
+pub fn build_chunk_mesh_no_ao(chunks_refs: &ChunksRefs, _lod: Lod) -> Option<ChunkMesh> {
     let mut mesh = ChunkMesh::default();
-    for i in 0..32 * 32 * 32 {
+    for i in 0..CHUNK_SIZE3 as i32 {
         let local = index_to_ivec3(i);
         let (current, back, left, down) = chunks_refs.get_adjacent_blocks(local);
         match current.block_type.is_solid() {
@@ -146,7 +148,7 @@ pub fn ambient_corner_voxels_cloned(
 
 pub fn build_chunk_mesh_ao(chunks_refs: &ChunksRefs, _lod: Lod) -> Option<ChunkMesh> {
     let mut mesh = ChunkMesh::default();
-    for i in 0..32 * 32 * 32 {
+    for i in 0..CHUNK_SIZE3 as i32 {
         let local = index_to_ivec3(i);
         let (current, back, left, down) = chunks_refs.get_adjacent_blocks(local);
         match current.block_type.is_solid() {
@@ -216,6 +218,7 @@ pub fn build_chunk_mesh_ao(chunks_refs: &ChunksRefs, _lod: Lod) -> Option<ChunkM
             }
         }
     }
+    append_fluid_vertices(chunks_refs, &mut mesh.vertices);
     if mesh.vertices.is_empty() {
         None
     } else {
@@ -12,6 +12,7 @@ use bevy::{math::ivec3, prelude::*};
 use crate::{
     chunk_mesh::ChunkMesh,
     chunks_refs::ChunksRefs,
+    constants::CHUNK_SIZE3,
     lod::Lod,
     quad::{Direction, Quad},
     utils::{generate_indices, index_to_ivec3, is_on_edge, make_vertex_u32},
@@ -51,7 +52,7 @@ fn push_face(
     }
 }
 
-pub fn build_chunk_mesh(chunks_refs: ChunksRefs, lod: Lod) -> Option<ChunkMesh> {
+pub fn build_chunk_mesh(chunks_refs: &ChunksRefs, lod: Lod) -> Option<ChunkMesh> {
     let mut mesh = ChunkMesh::default();
     // estimate if chunk is mostly solid or air
     let most_solid = chunks_refs
@@ -60,7 +61,7 @@ pub fn build_chunk_mesh(chunks_refs: ChunksRefs, lod: Lod) -> Option<ChunkMesh>
         .is_solid();
     // let most_solid = true;
 
-    for i in 0..32 * 32 * 32 {
+    for i in 0..CHUNK_SIZE3 as i32 {
         let local = index_to_ivec3(i);
         let current = chunks_refs.get_block(local);
         if match most_solid {
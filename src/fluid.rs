@@ -0,0 +1,396 @@
+use std::sync::Arc;
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    block_tick::{BlockTick, BlockTickPlugin, BlockTickScheduler, BlockTickSet},
+    chunk::ChunkData,
+    chunks_refs::ChunksRefs,
+    constants::CHUNK_SIZE,
+    packed_quad::pack_fluid_quad,
+    quad::{Direction, Quad},
+    utils::{index_to_ivec3, make_fluid_vertex_u32, world_voxel_to_chunk},
+    voxel::{BlockData, BlockType, FLUID_SOURCE_LEVEL},
+    voxel_engine::{
+        get_world_block, start_modifications, ChunkModification, VoxelEngine, VoxelModified,
+    },
+};
+
+const HORIZONTAL_DIRS: [IVec3; 4] = [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z];
+
+/// level of fluid falling down, or flowing out right next to a source
+pub const FLUID_FALLING_LEVEL: u8 = FLUID_SOURCE_LEVEL - 1;
+
+pub struct FluidPlugin;
+
+impl Plugin for FluidPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<BlockTickPlugin>() {
+            app.add_plugins(BlockTickPlugin);
+        }
+        app.add_systems(Update, tick_fluids.in_set(BlockTickSet));
+        app.add_systems(Update, schedule_fluids.after(start_modifications));
+    }
+}
+
+/// block ticks between two updates of a fluid voxel
+pub fn fluid_tick_delay(block_type: BlockType) -> u64 {
+    match block_type {
+        BlockType::Lava => 6,
+        _ => 2,
+    }
+}
+
+/// level lost for every voxel a fluid spreads sideways
+pub fn fluid_decay(block_type: BlockType) -> u8 {
+    match block_type {
+        BlockType::Lava => 2,
+        _ => 1,
+    }
+}
+
+// level a flowing voxel should have, based on what is feeding it. 0 means it dried up
+fn expected_level(
+    world_data: &HashMap<IVec3, Arc<ChunkData>>,
+    world_pos: IVec3,
+    block_type: BlockType,
+) -> u8 {
+    let same_fluid =
+        |pos: IVec3| get_world_block(world_data, pos).filter(|b| b.block_type == block_type);
+    if same_fluid(world_pos + IVec3::Y).is_some() {
+        return FLUID_FALLING_LEVEL;
+    }
+    HORIZONTAL_DIRS
+        .iter()
+        .filter_map(|dir| same_fluid(world_pos + *dir))
+        .map(|b| b.fluid_level.min(FLUID_SOURCE_LEVEL))
+        .max()
+        .map_or(0, |level| level.saturating_sub(fluid_decay(block_type)))
+}
+
+/// one cellular automaton step for a fluid voxel
+/// returns the blocks to place, nothing is written to world_data
+pub fn fluid_update(
+    world_data: &HashMap<IVec3, Arc<ChunkData>>,
+    world_pos: IVec3,
+    block: BlockData,
+) -> Vec<(IVec3, BlockData)> {
+    let mut changes = vec![];
+    let fluid = block.block_type;
+    let mut level = block.fluid_level;
+
+    // sources never change, flowing fluid follows its neighbours
+    if level < FLUID_SOURCE_LEVEL {
+        let expected = expected_level(world_data, world_pos, fluid);
+        if expected == 0 {
+            changes.push((world_pos, BlockType::Air.into()));
+            return changes;
+        }
+        if expected != level {
+            level = expected;
+            changes.push((world_pos, BlockData::fluid(fluid, level)));
+        }
+    }
+
+    // flow down first
+    let below = world_pos - IVec3::Y;
+    let Some(below_block) = get_world_block(world_data, below) else {
+        return changes;
+    };
+    if below_block.block_type == BlockType::Air
+        || (below_block.block_type == fluid && below_block.fluid_level < FLUID_FALLING_LEVEL)
+    {
+        changes.push((below, BlockData::fluid(fluid, FLUID_FALLING_LEVEL)));
+        return changes;
+    }
+    // falling onto falling fluid, wait for it to land
+    if below_block.block_type == fluid && below_block.fluid_level < FLUID_SOURCE_LEVEL {
+        return changes;
+    }
+
+    // spread sideways with decreasing level
+    let next_level = level.saturating_sub(fluid_decay(fluid));
+    if next_level == 0 {
+        return changes;
+    }
+    for dir in HORIZONTAL_DIRS {
+        let pos = world_pos + dir;
+        let Some(neighbour) = get_world_block(world_data, pos) else {
+            continue;
+        };
+        let replaceable = neighbour.block_type == BlockType::Air
+            || (neighbour.block_type == fluid && neighbour.fluid_level < next_level);
+        if replaceable {
+            changes.push((pos, BlockData::fluid(fluid, next_level)));
+        }
+    }
+    changes
+}
+
+/// run fluid updates for due block ticks, feeding the results through chunk_modifications
+fn tick_fluids(mut ticks: EventReader<BlockTick>, mut voxel_engine: ResMut<VoxelEngine>) {
+    // several fluids may write the same voxel, the highest level wins
+    let mut changes: HashMap<IVec3, BlockData> = HashMap::new();
+    for tick in ticks.read() {
+        if !tick.block.block_type.is_fluid() {
+            continue;
+        }
+        for (pos, block) in fluid_update(&voxel_engine.world_data, tick.world_pos, tick.block) {
+            changes
+                .entry(pos)
+                .and_modify(|b| {
+                    if block.fluid_level > b.fluid_level {
+                        *b = block;
+                    }
+                })
+                .or_insert(block);
+        }
+    }
+    for (world_pos, block) in changes {
+        let (chunk_pos, local_pos) = world_voxel_to_chunk(world_pos);
+        voxel_engine
+            .chunk_modifications
+            .entry(chunk_pos)
+            .or_default()
+            .push(ChunkModification(local_pos, block));
+    }
+}
+
+/// wake up fluids touching any modified voxel
+fn schedule_fluids(
+    mut modified: EventReader<VoxelModified>,
+    voxel_engine: Res<VoxelEngine>,
+    mut scheduler: ResMut<BlockTickScheduler>,
+) {
+    for event in modified.read() {
+        for offset in [IVec3::ZERO, IVec3::Y, IVec3::NEG_Y]
+            .into_iter()
+            .chain(HORIZONTAL_DIRS)
+        {
+            let pos = event.world_pos + offset;
+            let Some(block) = get_world_block(&voxel_engine.world_data, pos) else {
+                continue;
+            };
+            if block.block_type.is_fluid() {
+                scheduler.schedule(pos, fluid_tick_delay(block.block_type));
+            }
+        }
+    }
+}
+
+/// append quads for the fluid voxels of the middle chunk
+/// fluid faces are only culled by solids or the same fluid,
+/// their top vertices are lowered based on the fluid level
+pub fn append_fluid_vertices(chunks_refs: &ChunksRefs, vertices: &mut Vec<u32>) {
+    for_each_fluid_face(chunks_refs, |dir, local, quad_pos, drop, block_type| {
+        let quad = Quad::from_direction(dir, quad_pos, Color::BLUE);
+        for corner in quad.corners {
+            let corner = IVec3::from_array(corner);
+            let corner_drop = match corner.y > local.y {
+                true => drop,
+                false => 0,
+            };
+            vertices.push(make_fluid_vertex_u32(
+                corner,
+                corner_drop,
+                dir.get_normal() as u32,
+                block_type as u32,
+            ));
+        }
+    });
+}
+
+/// same faces as append_fluid_vertices, packed as quads (see packed_quad)
+pub fn append_fluid_quads(chunks_refs: &ChunksRefs, quads: &mut Vec<u64>) {
+    for_each_fluid_face(chunks_refs, |dir, _local, quad_pos, drop, block_type| {
+        quads.push(pack_fluid_quad(
+            quad_pos,
+            dir.get_normal() as u32,
+            block_type as u32,
+            drop,
+        ));
+    });
+}
+
+// calls f(direction, voxel position, quad minimum corner, drop, block type)
+// for every visible fluid face of the middle chunk
+fn for_each_fluid_face(
+    chunks_refs: &ChunksRefs,
+    mut f: impl FnMut(Direction, IVec3, IVec3, u32, BlockType),
+) {
+    let chunk = &chunks_refs.chunks[13];
+    if chunk
+        .get_block_if_filled()
+        .is_some_and(|b| !b.block_type.is_fluid())
+    {
+        return;
+    }
+    for i in 0..CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE {
+        let block = chunk.get_block(i);
+        if !block.block_type.is_fluid() {
+            continue;
+        }
+        let local = index_to_ivec3(i as i32);
+        let above = chunks_refs.get_block(local + IVec3::Y);
+        // a fluid with the same fluid above fills the voxel completely
+        let drop = match above.block_type == block.block_type {
+            true => 0,
+            false => (FLUID_SOURCE_LEVEL - block.fluid_level.min(FLUID_SOURCE_LEVEL)) as u32,
+        };
+
+        for (dir, offset, quad_offset) in [
+            (Direction::Left, IVec3::NEG_X, IVec3::ZERO),
+            (Direction::Right, IVec3::X, IVec3::X),
+            (Direction::Down, IVec3::NEG_Y, IVec3::ZERO),
+            (Direction::Up, IVec3::Y, IVec3::Y),
+            (Direction::Back, IVec3::NEG_Z, IVec3::ZERO),
+            (Direction::Forward, IVec3::Z, IVec3::Z),
+        ] {
+            let neighbour = chunks_refs.get_block(local + offset);
+            if neighbour.block_type.is_solid() || neighbour.block_type == block.block_type {
+                continue;
+            }
+            f(dir, local, local + quad_offset, drop, block.block_type);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        constants::{CHUNK_SIZE3, CHUNK_SIZE_I32},
+        test_utils::{chunk_from_fn, flat_world, set_block},
+    };
+
+    fn block_at(world_data: &HashMap<IVec3, Arc<ChunkData>>, pos: IVec3) -> BlockData {
+        *get_world_block(world_data, pos).unwrap()
+    }
+
+    // update every fluid in chunk (0,0,0) until nothing changes
+    fn settle(world_data: &mut HashMap<IVec3, Arc<ChunkData>>) {
+        for _ in 0..64 {
+            let mut changes: HashMap<IVec3, BlockData> = HashMap::new();
+            for i in 0..CHUNK_SIZE3 as i32 {
+                let pos = index_to_ivec3(i);
+                let block = block_at(world_data, pos);
+                if !block.block_type.is_fluid() {
+                    continue;
+                }
+                for (p, b) in fluid_update(world_data, pos, block) {
+                    let entry = changes.entry(p).or_insert(b);
+                    if b.fluid_level > entry.fluid_level {
+                        *entry = b;
+                    }
+                }
+            }
+            if changes.is_empty() {
+                return;
+            }
+            for (p, b) in changes {
+                set_block(world_data, p, b);
+            }
+        }
+        panic!("fluid never settled");
+    }
+
+    #[test]
+    fn source_spreads_sideways_on_the_ground() {
+        let mut world_data = flat_world(-1..=1);
+        let source = IVec3::new(5, 0, 5);
+        set_block(&mut world_data, source, BlockType::Water.into());
+        let changes = fluid_update(&world_data, source, BlockType::Water.into());
+        assert_eq!(changes.len(), 4);
+        for (pos, block) in changes {
+            let d = (pos - source).abs();
+            assert_eq!(d.x + d.y + d.z, 1);
+            assert_eq!(pos.y, 0);
+            assert_eq!(block, BlockData::fluid(BlockType::Water, 7));
+        }
+    }
+
+    #[test]
+    fn falls_before_spreading() {
+        let mut world_data = flat_world(-1..=1);
+        let source = IVec3::new(5, 3, 5);
+        set_block(&mut world_data, source, BlockType::Water.into());
+        let changes = fluid_update(&world_data, source, BlockType::Water.into());
+        assert_eq!(
+            changes,
+            [(
+                IVec3::new(5, 2, 5),
+                BlockData::fluid(BlockType::Water, FLUID_FALLING_LEVEL)
+            )]
+        );
+    }
+
+    #[test]
+    fn flowing_fluid_without_source_dries_up() {
+        let mut world_data = flat_world(-1..=1);
+        let pos = IVec3::new(5, 0, 5);
+        let flowing = BlockData::fluid(BlockType::Water, 3);
+        set_block(&mut world_data, pos, flowing);
+        let changes = fluid_update(&world_data, pos, flowing);
+        assert_eq!(changes, [(pos, BlockType::Air.into())]);
+    }
+
+    #[test]
+    fn water_settles_into_a_puddle() {
+        let mut world_data = flat_world(-1..=1);
+        let source = IVec3::new(CHUNK_SIZE_I32 / 2, 0, CHUNK_SIZE_I32 / 2);
+        set_block(&mut world_data, source, BlockType::Water.into());
+        settle(&mut world_data);
+        for d in 1..=7 {
+            let block = block_at(&world_data, source + IVec3::X * d);
+            assert_eq!(block, BlockData::fluid(BlockType::Water, 8 - d as u8));
+        }
+        assert_eq!(
+            block_at(&world_data, source + IVec3::X * 8).block_type,
+            BlockType::Air
+        );
+
+        // without its source the puddle drains away
+        set_block(&mut world_data, source, BlockType::Air.into());
+        settle(&mut world_data);
+        for i in 0..CHUNK_SIZE3 as i32 {
+            let pos = index_to_ivec3(i);
+            assert!(!block_at(&world_data, pos).block_type.is_fluid(), "{pos}");
+        }
+    }
+
+    #[test]
+    fn lava_spreads_less_than_water() {
+        let mut world_data = flat_world(-1..=1);
+        let source = IVec3::new(CHUNK_SIZE_I32 / 2, 0, CHUNK_SIZE_I32 / 2);
+        set_block(&mut world_data, source, BlockType::Lava.into());
+        settle(&mut world_data);
+        assert_eq!(
+            block_at(&world_data, source + IVec3::X * 3),
+            BlockData::fluid(BlockType::Lava, 2)
+        );
+        assert_eq!(
+            block_at(&world_data, source + IVec3::X * 4).block_type,
+            BlockType::Air
+        );
+    }
+
+    #[test]
+    fn fluid_faces_are_lowered_by_level() {
+        let water = IVec3::new(4, 4, 4);
+        let chunks_refs = ChunksRefs::with_middle(chunk_from_fn(|pos| match pos == water {
+            true => BlockData::fluid(BlockType::Water, 5),
+            false => BlockData::default(),
+        }));
+
+        let mut vertices = vec![];
+        append_fluid_vertices(&chunks_refs, &mut vertices);
+        assert_eq!(vertices.len(), 6 * 4);
+        for v in vertices {
+            let y = v >> 6 & 63;
+            let drop = v >> 18 & 7;
+            assert_eq!(v >> 24 & 1, 1);
+            assert_eq!(v >> 25, BlockType::Water as u32);
+            assert_eq!(drop, if y == 5 { 3 } else { 0 });
+        }
+    }
+}
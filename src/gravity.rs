@@ -0,0 +1,239 @@
+use std::sync::Arc;
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    block_tick::{BlockTick, BlockTickPlugin, BlockTickScheduler, BlockTickSet},
+    chunk::ChunkData,
+    utils::world_voxel_to_chunk,
+    voxel::{BlockData, BlockType},
+    voxel_engine::{
+        get_world_block, start_modifications, ChunkModification, VoxelEngine, VoxelModified,
+    },
+};
+
+/// block ticks between losing support and falling
+pub const GRAVITY_TICK_DELAY: u64 = 1;
+/// a column never falls further than this in a single collapse
+pub const MAX_FALL_DISTANCE: i32 = 128;
+
+pub struct GravityPlugin;
+
+impl Plugin for GravityPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<BlockTickPlugin>() {
+            app.add_plugins(BlockTickPlugin);
+        }
+        app.add_systems(Update, tick_gravity.in_set(BlockTickSet));
+        app.add_systems(Update, schedule_gravity.after(start_modifications));
+    }
+}
+
+/// drops the column of gravity blocks starting at world_pos onto the first solid voxel below
+/// returns the blocks to place, nothing is written to world_data.
+/// unloaded chunks count as support, the column waits on top of them
+pub fn collapse_column(
+    world_data: &HashMap<IVec3, Arc<ChunkData>>,
+    world_pos: IVec3,
+) -> Vec<(IVec3, BlockData)> {
+    let mut fall = 0;
+    while fall < MAX_FALL_DISTANCE
+        && get_world_block(world_data, world_pos - IVec3::Y * (fall + 1))
+            .is_some_and(|b| !b.block_type.is_solid())
+    {
+        fall += 1;
+    }
+    if fall == 0 {
+        return vec![];
+    }
+
+    // everything resting on top of the bottom block comes down with it
+    let column: Vec<BlockData> = (0..)
+        .map_while(|i| {
+            get_world_block(world_data, world_pos + IVec3::Y * i)
+                .filter(|b| b.block_type.has_gravity())
+                .copied()
+        })
+        .collect();
+    let height = column.len() as i32;
+
+    let mut changes = vec![];
+    for (i, block) in column.into_iter().enumerate() {
+        changes.push((world_pos + IVec3::Y * (i as i32 - fall), block));
+    }
+    // the top of the old column is left empty, whatever it fell through is replaced
+    for i in (height - fall).max(0)..height {
+        changes.push((world_pos + IVec3::Y * i, BlockType::Air.into()));
+    }
+    changes
+}
+
+/// collapse columns for due block ticks, feeding the results through chunk_modifications
+fn tick_gravity(mut ticks: EventReader<BlockTick>, mut voxel_engine: ResMut<VoxelEngine>) {
+    let mut changes = vec![];
+    for tick in ticks.read() {
+        if !tick.block.block_type.has_gravity() {
+            continue;
+        }
+        changes.extend(collapse_column(&voxel_engine.world_data, tick.world_pos));
+    }
+    for (world_pos, block) in changes {
+        let (chunk_pos, local_pos) = world_voxel_to_chunk(world_pos);
+        voxel_engine
+            .chunk_modifications
+            .entry(chunk_pos)
+            .or_default()
+            .push(ChunkModification(local_pos, block));
+    }
+}
+
+/// schedule gravity blocks that were placed, or lost the block below them
+fn schedule_gravity(
+    mut modified: EventReader<VoxelModified>,
+    voxel_engine: Res<VoxelEngine>,
+    mut scheduler: ResMut<BlockTickScheduler>,
+) {
+    for event in modified.read() {
+        if event.block.block_type.has_gravity() {
+            scheduler.schedule(event.world_pos, GRAVITY_TICK_DELAY);
+        }
+        if event.block.block_type.is_solid() {
+            continue;
+        }
+        let above = event.world_pos + IVec3::Y;
+        if get_world_block(&voxel_engine.world_data, above)
+            .is_some_and(|b| b.block_type.has_gravity())
+        {
+            scheduler.schedule(above, GRAVITY_TICK_DELAY);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{flat_world, set_block};
+
+    fn block_type_at(world_data: &HashMap<IVec3, Arc<ChunkData>>, pos: IVec3) -> BlockType {
+        get_world_block(world_data, pos).unwrap().block_type
+    }
+
+    fn apply(world_data: &mut HashMap<IVec3, Arc<ChunkData>>, changes: Vec<(IVec3, BlockData)>) {
+        for (pos, block) in changes {
+            set_block(world_data, pos, block);
+        }
+    }
+
+    #[test]
+    fn supported_blocks_stay() {
+        let mut world_data = flat_world(-1..=1);
+        let pos = IVec3::new(3, 0, 3);
+        set_block(&mut world_data, pos, BlockType::Sand.into());
+        assert!(collapse_column(&world_data, pos).is_empty());
+    }
+
+    #[test]
+    fn column_lands_on_the_ground() {
+        let mut world_data = flat_world(-1..=1);
+        // sand and gravel stacked in the air, with dirt on top that stays put
+        let bottom = IVec3::new(3, 10, 3);
+        set_block(&mut world_data, bottom, BlockType::Sand.into());
+        set_block(&mut world_data, bottom + IVec3::Y, BlockType::Gravel.into());
+        set_block(
+            &mut world_data,
+            bottom + IVec3::Y * 2,
+            BlockType::Sand.into(),
+        );
+        set_block(
+            &mut world_data,
+            bottom + IVec3::Y * 3,
+            BlockType::Dirt.into(),
+        );
+
+        let changes = collapse_column(&world_data, bottom);
+        apply(&mut world_data, changes);
+        let column = |y: i32| block_type_at(&world_data, IVec3::new(3, y, 3));
+        assert_eq!(column(0), BlockType::Sand);
+        assert_eq!(column(1), BlockType::Gravel);
+        assert_eq!(column(2), BlockType::Sand);
+        for y in 3..13 {
+            assert_eq!(column(y), BlockType::Air, "{y}");
+        }
+        assert_eq!(column(13), BlockType::Dirt);
+        assert!(collapse_column(&world_data, IVec3::new(3, 0, 3)).is_empty());
+    }
+
+    #[test]
+    fn short_fall_keeps_the_column_together() {
+        let mut world_data = flat_world(-1..=1);
+        let bottom = IVec3::new(3, 1, 3);
+        for y in 0..4 {
+            set_block(
+                &mut world_data,
+                bottom + IVec3::Y * y,
+                BlockType::Sand.into(),
+            );
+        }
+        let changes = collapse_column(&world_data, bottom);
+        apply(&mut world_data, changes);
+        for y in 0..4 {
+            assert_eq!(
+                block_type_at(&world_data, IVec3::new(3, y, 3)),
+                BlockType::Sand
+            );
+        }
+        assert_eq!(
+            block_type_at(&world_data, IVec3::new(3, 4, 3)),
+            BlockType::Air
+        );
+    }
+
+    #[test]
+    fn falls_through_fluids() {
+        let mut world_data = flat_world(-1..=1);
+        set_block(
+            &mut world_data,
+            IVec3::new(3, 0, 3),
+            BlockType::Water.into(),
+        );
+        set_block(
+            &mut world_data,
+            IVec3::new(3, 1, 3),
+            BlockType::Water.into(),
+        );
+        set_block(
+            &mut world_data,
+            IVec3::new(3, 5, 3),
+            BlockType::Gravel.into(),
+        );
+        let changes = collapse_column(&world_data, IVec3::new(3, 5, 3));
+        apply(&mut world_data, changes);
+        assert_eq!(
+            block_type_at(&world_data, IVec3::new(3, 0, 3)),
+            BlockType::Gravel
+        );
+        assert_eq!(
+            block_type_at(&world_data, IVec3::new(3, 1, 3)),
+            BlockType::Water
+        );
+        assert_eq!(
+            block_type_at(&world_data, IVec3::new(3, 5, 3)),
+            BlockType::Air
+        );
+    }
+
+    #[test]
+    fn unloaded_chunks_hold_the_column() {
+        let mut world_data = flat_world(-1..=1);
+        world_data.remove(&IVec3::new(0, -1, 0));
+        set_block(&mut world_data, IVec3::new(3, 5, 3), BlockType::Sand.into());
+        let changes = collapse_column(&world_data, IVec3::new(3, 5, 3));
+        assert_eq!(
+            changes,
+            [
+                (IVec3::new(3, 0, 3), BlockType::Sand.into()),
+                (IVec3::new(3, 5, 3), BlockType::Air.into()),
+            ]
+        );
+    }
+}
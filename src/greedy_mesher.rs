@@ -7,21 +7,22 @@ use crate::{
     chunk::ChunkData,
     chunk_mesh::ChunkMesh,
     chunks_refs::ChunksRefs,
+    constants::{PlaneRow, CHUNK_SIZE, CHUNK_SIZE_I32, CHUNK_SIZE_P},
     face_direction::FaceDir,
     lod::Lod,
     utils::{generate_indices, make_vertex_u32},
     voxel::MESHABLE_BLOCK_TYPES,
 };
 
-pub fn build_chunk_mesh(chunks_refs: ChunksRefs, lod: Lod) -> Option<ChunkMesh> {
+pub fn build_chunk_mesh(chunks_refs: &ChunksRefs, lod: Lod) -> Option<ChunkMesh> {
     let mut mesh = ChunkMesh::default();
     let mut quads = vec![];
-    quads.extend(vertices_from_face(FaceDir::Up, &chunks_refs, &lod));
-    quads.extend(vertices_from_face(FaceDir::Left, &chunks_refs, &lod));
-    // quads.extend(vertices_from_face(FaceDir::Right, &chunks_refs, &lod));
-    // quads.extend(vertices_from_face(FaceDir::Down, &chunks_refs, &lod));
-    quads.extend(vertices_from_face(FaceDir::Forward, &chunks_refs, &lod));
-    // quads.extend(vertices_from_face(FaceDir::Back, &chunks_refs, &lod));
+    quads.extend(vertices_from_face(FaceDir::Up, chunks_refs, &lod));
+    quads.extend(vertices_from_face(FaceDir::Left, chunks_refs, &lod));
+    // quads.extend(vertices_from_face(FaceDir::Right, chunks_refs, &lod));
+    // quads.extend(vertices_from_face(FaceDir::Down, chunks_refs, &lod));
+    quads.extend(vertices_from_face(FaceDir::Forward, chunks_refs, &lod));
+    // quads.extend(vertices_from_face(FaceDir::Back, chunks_refs, &lod));
     mesh.vertices.extend(quads);
     if mesh.vertices.is_empty() {
         None
@@ -31,19 +32,19 @@ pub fn build_chunk_mesh(chunks_refs: ChunksRefs, lod: Lod) -> Option<ChunkMesh>
     }
 }
 
-pub fn build_chunk_mesh_no_ao(chunks_refs: ChunksRefs, lod: Lod) -> Option<ChunkMesh> {
+pub fn build_chunk_mesh_no_ao(chunks_refs: &ChunksRefs, lod: Lod) -> Option<ChunkMesh> {
     let mut mesh = ChunkMesh::default();
     let mut quads = vec![];
-    quads.extend(vertices_from_face_no_ao(FaceDir::Up, &chunks_refs, &lod));
-    quads.extend(vertices_from_face_no_ao(FaceDir::Left, &chunks_refs, &lod));
-    quads.extend(vertices_from_face_no_ao(FaceDir::Right, &chunks_refs, &lod));
-    quads.extend(vertices_from_face_no_ao(FaceDir::Down, &chunks_refs, &lod));
+    quads.extend(vertices_from_face_no_ao(FaceDir::Up, chunks_refs, &lod));
+    quads.extend(vertices_from_face_no_ao(FaceDir::Left, chunks_refs, &lod));
+    quads.extend(vertices_from_face_no_ao(FaceDir::Right, chunks_refs, &lod));
+    quads.extend(vertices_from_face_no_ao(FaceDir::Down, chunks_refs, &lod));
     quads.extend(vertices_from_face_no_ao(
         FaceDir::Forward,
-        &chunks_refs,
+        chunks_refs,
         &lod,
     ));
-    quads.extend(vertices_from_face_no_ao(FaceDir::Back, &chunks_refs, &lod));
+    quads.extend(vertices_from_face_no_ao(FaceDir::Back, chunks_refs, &lod));
     mesh.vertices.extend(quads);
     if mesh.vertices.is_empty() {
         None
@@ -60,9 +61,9 @@ pub fn vertices_from_face(face_dir: FaceDir, chunks_refs: &ChunksRefs, lod: &Lod
     let size = lod.size();
     for axis in 0..size {
         // not optimal... save ambient occlusion data
-        let mut ao_data = [[0; 34]; 34];
-        for y in -1..33 {
-            for x in -1..33 {
+        let mut ao_data = [[0; CHUNK_SIZE_P]; CHUNK_SIZE_P];
+        for y in -1..=CHUNK_SIZE_I32 {
+            for x in -1..=CHUNK_SIZE_I32 {
                 let pos = face_dir.world_to_sample(axis, x, y, lod);
                 let pos = pos * lod.jump_index();
                 let is_solid = chunks_refs
@@ -74,7 +75,7 @@ pub fn vertices_from_face(face_dir: FaceDir, chunks_refs: &ChunksRefs, lod: &Lod
         }
 
         // key: ao + color's
-        let mut x_data = HashMap::<u32, [u32; 32]>::new();
+        let mut x_data = HashMap::<u32, [PlaneRow; CHUNK_SIZE]>::new();
         for i in 0..size * size {
             let x = i % size;
             let y = (i / size) as i32;
@@ -99,13 +100,13 @@ pub fn vertices_from_face(face_dir: FaceDir, chunks_refs: &ChunksRefs, lod: &Lod
             let data = match x_data.get_mut(&p_index) {
                 Some(d) => d,
                 None => {
-                    x_data.insert(p_index, [0u32; 32]);
+                    x_data.insert(p_index, [0; CHUNK_SIZE]);
                     x_data.get_mut(&p_index).unwrap()
                 }
             };
 
             // set bit to 1 or 0 depending if solid
-            data[x as usize] |= (1 << y) * is_solid as u32;
+            data[x as usize] |= (1 << y) * is_solid as PlaneRow;
         } // axis type loop
         for (p_index, data) in x_data.into_iter() {
             let quads_from_axis = greedy_mesh_binary_plane(data, lod.size() as u32);
@@ -130,7 +131,7 @@ pub fn vertices_from_face_no_ao(
     let size = lod.size();
     for axis in 0..size {
         for block_type in MESHABLE_BLOCK_TYPES.iter() {
-            let mut x_data = [0u32; 32];
+            let mut x_data = [0 as PlaneRow; CHUNK_SIZE];
             for i in 0..size * size {
                 let row = i % size;
                 let column = (i / size) as i32;
@@ -144,7 +145,8 @@ pub fn vertices_from_face_no_ao(
                 }
                 let is_solid = current.block_type.is_solid() && !neg_z_block.block_type.is_solid();
                 // set bit to 1 or 0 depending if solid
-                x_data[row as usize] = ((1 << column) * is_solid as u32) | x_data[row as usize];
+                x_data[row as usize] =
+                    ((1 << column) * is_solid as PlaneRow) | x_data[row as usize];
             }
             let quads_from_axis = greedy_mesh_binary_plane(x_data, lod.size() as u32);
             quads_from_axis
@@ -248,7 +250,10 @@ impl GreedyQuad {
 
 ///! generate quads of a binary slice
 ///! lod not implemented yet
-pub fn greedy_mesh_binary_plane(mut data: [u32; 32], lod_size: u32) -> Vec<GreedyQuad> {
+pub fn greedy_mesh_binary_plane(
+    mut data: [PlaneRow; CHUNK_SIZE],
+    lod_size: u32,
+) -> Vec<GreedyQuad> {
     let mut greedy_quads = vec![];
     for row in 0..data.len() {
         let mut y = 0;
@@ -263,7 +268,7 @@ pub fn greedy_mesh_binary_plane(mut data: [u32; 32], lod_size: u32) -> Vec<Greed
 
             // convert height 'num' to positive bits repeated 'num' times aka:
             // 1 = 0b1, 2 = 0b11, 4 = 0b1111
-            let h_as_mask = u32::checked_shl(1, h).map_or(!0, |v| v - 1);
+            let h_as_mask = PlaneRow::checked_shl(1, h).map_or(!0, |v| v - 1);
             // offset the mask to the correct y pos
             let mask = h_as_mask << y;
 
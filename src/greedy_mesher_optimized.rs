@@ -3,18 +3,46 @@ use std::{
     time::{Duration, Instant},
 };
 
-use bevy::{math::ivec3, prelude::*, utils::HashMap};
+use bevy::{math::ivec3, prelude::*};
 
 use crate::{
-    chunk_mesh::ChunkMesh,
+    chunk::ChunkData,
+    chunk_mesh::{ChunkCollisionMesh, ChunkMesh},
     chunks_refs::ChunksRefs,
-    constants::{ADJACENT_AO_DIRS, CHUNK_SIZE, CHUNK_SIZE_P, CHUNK_SIZE_P2, CHUNK_SIZE_P3},
+    constants::{
+        PlaneRow, ADJACENT_AO_DIRS, CHUNK_SIZE, CHUNK_SIZE_P, CHUNK_SIZE_P2, CHUNK_SIZE_P3,
+    },
     face_direction::FaceDir,
+    fluid::{append_fluid_quads, append_fluid_vertices},
     lod::Lod,
-    utils::{generate_indices, make_vertex_u32, vec3_to_index},
+    packed_quad::pack_quad,
+    utils::{generate_indices, index_to_ivec3_bounds, make_vertex_u32, vec3_to_index},
 };
 
 pub fn build_chunk_mesh(chunks_refs: &ChunksRefs, lod: Lod) -> Option<ChunkMesh> {
+    build_chunk_mesh_internal(chunks_refs, lod, false, false)
+}
+
+/// same as build_chunk_mesh, but also fills ChunkMesh::collision
+pub fn build_chunk_mesh_with_collision(chunks_refs: &ChunksRefs, lod: Lod) -> Option<ChunkMesh> {
+    build_chunk_mesh_internal(chunks_refs, lod, true, false)
+}
+
+/// same as build_chunk_mesh, but fills ChunkMesh::quads instead of vertices and indices
+pub fn build_chunk_quads(
+    chunks_refs: &ChunksRefs,
+    lod: Lod,
+    build_collision: bool,
+) -> Option<ChunkMesh> {
+    build_chunk_mesh_internal(chunks_refs, lod, build_collision, true)
+}
+
+fn build_chunk_mesh_internal(
+    chunks_refs: &ChunksRefs,
+    lod: Lod,
+    build_collision: bool,
+    packed_quads: bool,
+) -> Option<ChunkMesh> {
     // early exit, if all faces are culled
     if chunks_refs.is_all_voxels_same() {
         return None;
@@ -22,72 +50,20 @@ pub fn build_chunk_mesh(chunks_refs: &ChunksRefs, lod: Lod) -> Option<ChunkMesh>
     let mut mesh = ChunkMesh::default();
 
     // solid binary for each x,y,z axis (3)
-    let mut axis_cols = [[[0u64; CHUNK_SIZE_P]; CHUNK_SIZE_P]; 3];
+    let mut axis_cols: AxisCols = [[[0u64; CHUNK_SIZE_P]; CHUNK_SIZE_P]; 3];
 
     // the cull mask to perform greedy slicing, based on solids on previous axis_cols
     let mut col_face_masks = [[[0u64; CHUNK_SIZE_P]; CHUNK_SIZE_P]; 6];
 
-    #[inline]
-    fn add_voxel_to_axis_cols(
-        b: &crate::voxel::BlockData,
-        x: usize,
-        y: usize,
-        z: usize,
-        axis_cols: &mut [[[u64; 34]; 34]; 3],
-    ) {
-        if b.block_type.is_solid() {
-            // x,z - y axis
-            axis_cols[0][z][x] |= 1u64 << y as u64;
-            // z,y - x axis
-            axis_cols[1][y][z] |= 1u64 << x as u64;
-            // x,y - z axis
-            axis_cols[2][y][x] |= 1u64 << z as u64;
-        }
-    }
-
     // inner chunk voxels.
     let chunk = &*chunks_refs.chunks[vec3_to_index(IVec3::new(1, 1, 1), 3)];
-    assert!(chunk.voxels.len() == CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE || chunk.voxels.len() == 1);
-    for z in 0..CHUNK_SIZE {
-        for y in 0..CHUNK_SIZE {
-            for x in 0..CHUNK_SIZE {
-                let i = match chunk.voxels.len() {
-                    1 => 0,
-                    _ => (z * CHUNK_SIZE + y) * CHUNK_SIZE + x,
-                };
-                add_voxel_to_axis_cols(&chunk.voxels[i], x + 1, y + 1, z + 1, &mut axis_cols)
-            }
-        }
-    }
+    assert!(
+        chunk.voxels().len() == CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE || chunk.voxels().len() == 1
+    );
+    add_chunk_to_axis_cols(chunk, &mut axis_cols);
 
     // neighbor chunk voxels.
-    // note(leddoo): couldn't be bothered to optimize these.
-    //  might be worth it though. together, they take
-    //  almost as long as the entire "inner chunk" loop.
-    for z in [0, CHUNK_SIZE_P - 1] {
-        for y in 0..CHUNK_SIZE_P {
-            for x in 0..CHUNK_SIZE_P {
-                let pos = ivec3(x as i32, y as i32, z as i32) - IVec3::ONE;
-                add_voxel_to_axis_cols(chunks_refs.get_block(pos), x, y, z, &mut axis_cols);
-            }
-        }
-    }
-    for z in 0..CHUNK_SIZE_P {
-        for y in [0, CHUNK_SIZE_P - 1] {
-            for x in 0..CHUNK_SIZE_P {
-                let pos = ivec3(x as i32, y as i32, z as i32) - IVec3::ONE;
-                add_voxel_to_axis_cols(chunks_refs.get_block(pos), x, y, z, &mut axis_cols);
-            }
-        }
-    }
-    for z in 0..CHUNK_SIZE_P {
-        for x in [0, CHUNK_SIZE_P - 1] {
-            for y in 0..CHUNK_SIZE_P {
-                let pos = ivec3(x as i32, y as i32, z as i32) - IVec3::ONE;
-                add_voxel_to_axis_cols(chunks_refs.get_block(pos), x, y, z, &mut axis_cols);
-            }
-        }
-    }
+    add_padding_to_axis_cols(chunks_refs, &mut axis_cols);
 
     // face culling
     for axis in 0..3 {
@@ -104,20 +80,15 @@ pub fn build_chunk_mesh(chunks_refs: &ChunksRefs, lod: Lod) -> Option<ChunkMesh>
         }
     }
 
-    // greedy meshing planes for every axis (6)
-    // key(block + ao) -> HashMap<axis(0-32), binary_plane>
-    // note(leddoo): don't ask me how this isn't a massive blottleneck.
-    //  might become an issue in the future, when there are more block types.
-    //  consider using a single hashmap with key (axis, block_hash, y).
-    let mut data: [HashMap<u32, HashMap<u32, [u32; 32]>>; 6];
-    data = [
-        HashMap::new(),
-        HashMap::new(),
-        HashMap::new(),
-        HashMap::new(),
-        HashMap::new(),
-        HashMap::new(),
-    ];
+    // greedy meshing planes for every axis (6), slice and block + ao
+    let mut planes = PlaneBuckets::default();
+
+    // collision planes ignore block type and ao, so quads can grow larger
+    // axis -> y -> binary_plane
+    let mut collision_planes = match build_collision {
+        true => Some(Box::new([[[0 as PlaneRow; CHUNK_SIZE]; CHUNK_SIZE]; 6])),
+        false => None,
+    };
 
     // find faces and build binary planes based on the voxel block+ao etc...
     for axis in 0..6 {
@@ -130,6 +101,10 @@ pub fn build_chunk_mesh(chunks_refs: &ChunksRefs, lod: Lod) -> Option<ChunkMesh>
                 col >>= 1;
                 // removes the left most padding value, because it's invalid
                 col &= !(1 << CHUNK_SIZE as u64);
+                if col == 0 {
+                    continue;
+                }
+                let ao_cols = ao_sample_cols(&axis_cols, axis, z, x);
 
                 while col != 0 {
                     let y = col.trailing_zeros();
@@ -144,62 +119,66 @@ pub fn build_chunk_mesh(chunks_refs: &ChunksRefs, lod: Lod) -> Option<ChunkMesh>
                     };
 
                     // calculate ambient occlusion
-                    let mut ao_index = 0;
-                    for (ao_i, ao_offset) in ADJACENT_AO_DIRS.iter().enumerate() {
-                        // ambient occlusion is sampled based on axis(ascent or descent)
-                        let ao_sample_offset = match axis {
-                            0 => ivec3(ao_offset.x, -1, ao_offset.y), // down
-                            1 => ivec3(ao_offset.x, 1, ao_offset.y),  // up
-                            2 => ivec3(-1, ao_offset.y, ao_offset.x), // left
-                            3 => ivec3(1, ao_offset.y, ao_offset.x),  // right
-                            4 => ivec3(ao_offset.x, ao_offset.y, -1), // forward
-                            _ => ivec3(ao_offset.x, ao_offset.y, 1),  // back
-                        };
-                        let ao_voxel_pos = voxel_pos + ao_sample_offset;
-                        let ao_block = chunks_refs.get_block(ao_voxel_pos);
-                        if ao_block.block_type.is_solid() {
-                            ao_index |= 1u32 << ao_i;
-                        }
-                    }
+                    let ao_index = ao_index_at(&ao_cols, y);
 
                     let current_voxel = chunks_refs.get_block_no_neighbour(voxel_pos);
                     // let current_voxel = chunks_refs.get_block(voxel_pos);
                     // we can only greedy mesh same block types + same ambient occlusion
                     let block_hash = ao_index | ((current_voxel.block_type as u32) << 9);
-                    let data = data[axis]
-                        .entry(block_hash)
-                        .or_default()
-                        .entry(y)
-                        .or_default();
-                    data[x as usize] |= 1u32 << z as u32;
+                    planes.insert(axis, y, block_hash, x, z as u32);
+                    if let Some(planes) = collision_planes.as_mut() {
+                        planes[axis][y as usize][x] |= 1 << z;
+                    }
                 }
             }
         }
     }
 
     let mut vertices = vec![];
-    for (axis, block_ao_data) in data.into_iter().enumerate() {
-        let facedir = match axis {
-            0 => FaceDir::Down,
-            1 => FaceDir::Up,
-            2 => FaceDir::Left,
-            3 => FaceDir::Right,
-            4 => FaceDir::Forward,
-            _ => FaceDir::Back,
-        };
-        for (block_ao, axis_plane) in block_ao_data.into_iter() {
-            let ao = block_ao & 0b111111111;
-            let block_type = block_ao >> 9;
-            for (axis_pos, plane) in axis_plane.into_iter() {
-                let quads_from_axis = greedy_mesh_binary_plane(plane, lod.size() as u32);
+    let mut quads = vec![];
+    planes.for_each(|axis, axis_pos, block_ao, plane| {
+        let facedir = axis_face_dir(axis);
+        let ao = block_ao & 0b111111111;
+        let block_type = block_ao >> 9;
+        let quads_from_axis = greedy_mesh_binary_plane(plane, lod.size() as u32);
 
-                quads_from_axis.into_iter().for_each(|q| {
+        quads_from_axis
+            .into_iter()
+            .for_each(|q| match packed_quads {
+                true => quads.push(q.pack(facedir, axis_pos, ao, block_type)),
+                false => {
                     q.append_vertices(&mut vertices, facedir, axis_pos, &Lod::L32, ao, block_type)
+                }
+            });
+    });
+
+    if let Some(planes) = collision_planes {
+        let mut collision = ChunkCollisionMesh::default();
+        for (axis, axis_planes) in planes.iter().enumerate() {
+            let facedir = axis_face_dir(axis);
+            for (axis_pos, plane) in axis_planes.iter().enumerate() {
+                if plane.iter().all(|row| *row == 0) {
+                    continue;
+                }
+                let quads_from_axis = greedy_mesh_binary_plane(*plane, lod.size() as u32);
+                quads_from_axis.into_iter().for_each(|q| {
+                    q.append_collision_positions(&mut collision.positions, facedir, axis_pos as u32)
                 });
             }
         }
+        collision.indices = generate_indices(collision.positions.len());
+        mesh.collision = Some(collision);
     }
 
+    if packed_quads {
+        append_fluid_quads(chunks_refs, &mut quads);
+        mesh.quads = quads;
+        return match mesh.quads.is_empty() {
+            true => None,
+            false => Some(mesh),
+        };
+    }
+    append_fluid_vertices(chunks_refs, &mut vertices);
     mesh.vertices.extend(vertices);
     if mesh.vertices.is_empty() {
         None
@@ -209,6 +188,173 @@ pub fn build_chunk_mesh(chunks_refs: &ChunksRefs, lod: Lod) -> Option<ChunkMesh>
     }
 }
 
+// solid binary columns of the padded chunk, along the y, x and z axis
+type AxisCols = [[[u64; CHUNK_SIZE_P]; CHUNK_SIZE_P]; 3];
+
+// solid samples of ADJACENT_AO_DIRS for the faces in column (z, x) of the face axis (0-5),
+// bit y of sample i is set when ao sample i of the face at y is solid.
+// samples sit one voxel along the face normal, in the padded columns of the face's axis
+#[inline]
+fn ao_sample_cols(axis_cols: &AxisCols, axis: usize, z: usize, x: usize) -> [u64; 9] {
+    let shift = (axis & 1) * 2;
+    ADJACENT_AO_DIRS.map(|offset| {
+        let z = (z as i32 + 1 + offset.y) as usize;
+        let x = (x as i32 + 1 + offset.x) as usize;
+        axis_cols[axis / 2][z][x] >> shift
+    })
+}
+
+// 9 bit ambient occlusion index of the face at y, see ao_sample_cols
+#[inline]
+fn ao_index_at(ao_cols: &[u64; 9], y: u32) -> u32 {
+    ao_cols
+        .iter()
+        .enumerate()
+        .fold(0, |ao, (i, col)| ao | ((col >> y) as u32 & 1) << i)
+}
+
+// bits start..end set
+#[inline]
+fn range_mask(start: usize, end: usize) -> u64 {
+    let len = (end - start) as u32;
+    u64::checked_shl(1, len).map_or(!0, |v| v - 1) << start
+}
+
+// mark a box of solid voxels in padded coordinates, min inclusive and max exclusive
+fn add_solid_box_to_axis_cols(min: [usize; 3], max: [usize; 3], axis_cols: &mut AxisCols) {
+    let [x_mask, y_mask, z_mask] = [0, 1, 2].map(|i| range_mask(min[i], max[i]));
+    // columns are indexed [z][x], [y][z] and [y][x]
+    for (axis, mask, outer, inner) in [(0, y_mask, 2, 0), (1, x_mask, 1, 2), (2, z_mask, 1, 0)] {
+        for cols in axis_cols[axis][min[outer]..max[outer]].iter_mut() {
+            cols[min[inner]..max[inner]]
+                .iter_mut()
+                .for_each(|col| *col |= mask);
+        }
+    }
+}
+
+// the middle chunk's cached occupancy, offset by the padding
+fn add_chunk_to_axis_cols(chunk: &ChunkData, axis_cols: &mut AxisCols) {
+    for (axis, cols) in axis_cols.iter_mut().enumerate() {
+        for a in 0..CHUNK_SIZE {
+            for b in 0..CHUNK_SIZE {
+                cols[a + 1][b + 1] |= (chunk.solid_column(axis, a, b) as u64) << 1;
+            }
+        }
+    }
+}
+
+// the 1 voxel padding around the middle chunk, read from the cached solid columns of the slab
+// each of the 26 neighbours shares with it. uniform neighbours are set as whole boxes
+fn add_padding_to_axis_cols(chunks_refs: &ChunksRefs, axis_cols: &mut AxisCols) {
+    // neighbour offset (0-2) -> range of its local coordinates, and where that range starts padded
+    let slab = |offset: i32| match offset {
+        0 => (CHUNK_SIZE - 1..CHUNK_SIZE, 0),
+        1 => (0..CHUNK_SIZE, 1),
+        _ => (0..1, CHUNK_SIZE_P - 1),
+    };
+    for (i, chunk) in chunks_refs.chunks.iter().enumerate() {
+        let offset = index_to_ivec3_bounds(i as i32, 3);
+        if offset == IVec3::ONE {
+            continue;
+        }
+        let (xs, px) = slab(offset.x);
+        let (ys, py) = slab(offset.y);
+        let (zs, pz) = slab(offset.z);
+        if let Some(block) = chunk.get_block_if_filled() {
+            if block.block_type.is_solid() {
+                let min = [px, py, pz];
+                let max = [px + xs.len(), py + ys.len(), pz + zs.len()];
+                add_solid_box_to_axis_cols(min, max, axis_cols);
+            }
+            continue;
+        }
+        // (column axis, [a][b] indices) of the columns along y, x and z, see AxisCols
+        let ranges = [xs, ys, zs];
+        let starts = [px, py, pz];
+        for (axis, (along, a, b)) in [(1, 2, 0), (0, 1, 2), (2, 1, 0)].into_iter().enumerate() {
+            let along_range = ranges[along].clone();
+            let mask = range_mask(along_range.start, along_range.end);
+            for col_a in ranges[a].clone() {
+                for col_b in ranges[b].clone() {
+                    let col = chunk.solid_column(axis, col_a, col_b) as u64 & mask;
+                    let padded_a = col_a - ranges[a].start + starts[a];
+                    let padded_b = col_b - ranges[b].start + starts[b];
+                    axis_cols[axis][padded_a][padded_b] |=
+                        col >> along_range.start << starts[along];
+                }
+            }
+        }
+    }
+}
+
+/// binary planes of a chunk, bucketed by (face, slice, block + ao)
+/// every face and slice keeps its block + ao keys sorted, pointing into one flat list of planes,
+/// so lookups stay cheap with thousands of block types and iteration order is deterministic
+pub struct PlaneBuckets {
+    // indexed by face * CHUNK_SIZE + slice, (block + ao, index into planes)
+    slices: Vec<Vec<(u32, u32)>>,
+    planes: Vec<[PlaneRow; CHUNK_SIZE]>,
+}
+
+impl Default for PlaneBuckets {
+    fn default() -> Self {
+        Self {
+            slices: vec![Vec::new(); 6 * CHUNK_SIZE],
+            planes: Vec::new(),
+        }
+    }
+}
+
+impl PlaneBuckets {
+    /// set bit of row in the plane of this face, slice and block + ao
+    #[inline]
+    pub fn insert(&mut self, face: usize, slice: u32, block_ao: u32, row: usize, bit: u32) {
+        let keys = &mut self.slices[face * CHUNK_SIZE + slice as usize];
+        let plane = match keys.binary_search_by_key(&block_ao, |(key, _)| *key) {
+            Ok(i) => keys[i].1,
+            Err(i) => {
+                let plane = self.planes.len() as u32;
+                self.planes.push([0; CHUNK_SIZE]);
+                keys.insert(i, (block_ao, plane));
+                plane
+            }
+        };
+        self.planes[plane as usize][row] |= 1 << bit;
+    }
+
+    /// calls f(face, slice, block + ao, plane) ordered by face, slice and block + ao
+    pub fn for_each(&self, mut f: impl FnMut(usize, u32, u32, [PlaneRow; CHUNK_SIZE])) {
+        for (i, keys) in self.slices.iter().enumerate() {
+            for (block_ao, plane) in keys.iter() {
+                let (face, slice) = (i / CHUNK_SIZE, (i % CHUNK_SIZE) as u32);
+                f(face, slice, *block_ao, self.planes[*plane as usize]);
+            }
+        }
+    }
+
+    /// number of non empty planes
+    pub fn len(&self) -> usize {
+        self.planes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.planes.is_empty()
+    }
+}
+
+// face direction of the axis used by col_face_masks
+fn axis_face_dir(axis: usize) -> FaceDir {
+    match axis {
+        0 => FaceDir::Down,
+        1 => FaceDir::Up,
+        2 => FaceDir::Left,
+        3 => FaceDir::Right,
+        4 => FaceDir::Forward,
+        _ => FaceDir::Back,
+    }
+}
+
 // todo: compress further?
 #[derive(Debug)]
 pub struct GreedyQuad {
@@ -235,10 +381,7 @@ impl GreedyQuad {
         let jump = lod.jump_index();
 
         // pack ambient occlusion strength into vertex
-        let v1ao = ((ao >> 0) & 1) + ((ao >> 1) & 1) + ((ao >> 3) & 1);
-        let v2ao = ((ao >> 3) & 1) + ((ao >> 6) & 1) + ((ao >> 7) & 1);
-        let v3ao = ((ao >> 5) & 1) + ((ao >> 8) & 1) + ((ao >> 7) & 1);
-        let v4ao = ((ao >> 1) & 1) + ((ao >> 2) & 1) + ((ao >> 5) & 1);
+        let [v1ao, v2ao, v3ao, v4ao] = corner_ao(ao);
 
         let v1 = make_vertex_u32(
             face_dir.world_to_sample(axis as i32, self.x as i32, self.y as i32, &lod) * jump,
@@ -300,13 +443,75 @@ impl GreedyQuad {
 
         vertices.extend(new_vertices);
     }
+
+    /// compress this quad into a single u64, see packed_quad
+    pub fn pack(&self, face_dir: FaceDir, axis: u32, ao: u32, block_type: u32) -> u64 {
+        let corner_ao = corner_ao(ao);
+        pack_quad(
+            face_dir.world_to_sample(axis as i32, self.x as i32, self.y as i32, &Lod::L32),
+            self.w,
+            self.h,
+            face_dir.normal_index(),
+            block_type,
+            corner_ao,
+            // anisotropy flip, same as append_vertices
+            (corner_ao[0] > 0) ^ (corner_ao[2] > 0),
+        )
+    }
+
+    /// append the 4 corner positions of this quad, wound the same way as append_vertices
+    pub fn append_collision_positions(
+        &self,
+        positions: &mut Vec<[f32; 3]>,
+        face_dir: FaceDir,
+        axis: u32,
+    ) {
+        let axis = axis as i32;
+        let (x, y, w, h) = (self.x as i32, self.y as i32, self.w as i32, self.h as i32);
+        let mut corners = [
+            face_dir.world_to_sample(axis, x, y, &Lod::L32),
+            face_dir.world_to_sample(axis, x + w, y, &Lod::L32),
+            face_dir.world_to_sample(axis, x + w, y + h, &Lod::L32),
+            face_dir.world_to_sample(axis, x, y + h, &Lod::L32),
+        ];
+        if face_dir.reverse_order() {
+            // keep first index, but reverse the rest
+            corners[1..].reverse();
+        }
+        positions.extend(corners.map(|c| c.as_vec3().to_array()));
+    }
+}
+
+// ambient occlusion strength of the 4 quad corners, from the 9 bit ao sample mask
+#[inline]
+fn corner_ao(ao: u32) -> [u32; 4] {
+    [
+        ((ao >> 0) & 1) + ((ao >> 1) & 1) + ((ao >> 3) & 1),
+        ((ao >> 3) & 1) + ((ao >> 6) & 1) + ((ao >> 7) & 1),
+        ((ao >> 5) & 1) + ((ao >> 8) & 1) + ((ao >> 7) & 1),
+        ((ao >> 1) & 1) + ((ao >> 2) & 1) + ((ao >> 5) & 1),
+    ]
 }
 
 ///! generate quads of a binary slice
 ///! lod not implemented atm
-pub fn greedy_mesh_binary_plane(mut data: [u32; 32], lod_size: u32) -> Vec<GreedyQuad> {
+pub fn greedy_mesh_binary_plane(data: [PlaneRow; CHUNK_SIZE], lod_size: u32) -> Vec<GreedyQuad> {
+    greedy_mesh_binary_plane_u64(data.map(|row| row as u64), lod_size)
+}
+
+/// greedy_mesh_binary_plane over N rows of up to 64 bits.
+/// only bits inside the lod_size square are meshed, quads never extend past it
+pub fn greedy_mesh_binary_plane_u64<const N: usize>(
+    mut data: [u64; N],
+    lod_size: u32,
+) -> Vec<GreedyQuad> {
+    let lod_size = lod_size.min(u64::BITS);
+    let rows = N.min(lod_size as usize);
+    let lod_mask = u64::checked_shl(1, lod_size).map_or(!0, |v| v - 1);
+    data.iter_mut().for_each(|row| *row &= lod_mask);
+
     let mut greedy_quads = vec![];
-    for row in 0..data.len() {
+    for row in 0..rows {
         let mut y = 0;
         while y < lod_size {
             // find first solid, "air/zero's" could be first so skip
@@ -318,11 +523,11 @@ pub fn greedy_mesh_binary_plane(mut data: [u32; 32], lod_size: u32) -> Vec<Greed
             let h = (data[row] >> y).trailing_ones();
             // convert height 'num' to positive bits repeated 'num' times aka:
             // 1 = 0b1, 2 = 0b11, 4 = 0b1111
-            let h_as_mask = u32::checked_shl(1, h).map_or(!0, |v| v - 1);
+            let h_as_mask = u64::checked_shl(1, h).map_or(!0, |v| v - 1);
             let mask = h_as_mask << y;
             // grow horizontally
             let mut w = 1;
-            while row + w < lod_size as usize {
+            while row + w < rows {
                 // fetch bits spanning height, in the next row
                 let next_row_h = (data[row + w] >> y) & h_as_mask;
                 if next_row_h != h_as_mask {
@@ -345,3 +550,360 @@ pub fn greedy_mesh_binary_plane(mut data: [u32; 32], lod_size: u32) -> Vec<Greed
     }
     greedy_quads
 }
+
+/// checks that quads cover every set bit of the lod_size square of rows exactly once,
+/// and nothing outside of it. used by the property tests and the fuzz targets
+#[cfg(any(test, feature = "fuzzing"))]
+pub fn check_plane_tiling(rows: &[u64], lod_size: u32, quads: &[GreedyQuad]) -> Result<(), String> {
+    let lod_size = lod_size.min(u64::BITS);
+    let lod_mask = u64::checked_shl(1, lod_size).map_or(!0, |v| v - 1);
+    let size = rows.len().min(lod_size as usize);
+    let mut covered = vec![0u64; size];
+    for q in quads {
+        let (x, w) = (q.x as usize, q.w as usize);
+        if q.w == 0 || q.h == 0 || x + w > size || q.y + q.h > lod_size {
+            return Err(format!("{q:?} outside of {size}x{lod_size}"));
+        }
+        let mask = u64::checked_shl(1, q.h).map_or(!0, |v| v - 1) << q.y;
+        for (i, col) in covered[x..x + w].iter_mut().enumerate() {
+            if rows[x + i] & mask != mask {
+                return Err(format!("{q:?} covers unset bits of row {}", x + i));
+            }
+            if *col & mask != 0 {
+                return Err(format!("{q:?} overlaps another quad in row {}", x + i));
+            }
+            *col |= mask;
+        }
+    }
+    for (x, col) in covered.iter().enumerate() {
+        if *col != rows[x] & lod_mask {
+            return Err(format!(
+                "row {x} bits {:b} not covered",
+                rows[x] & lod_mask & !col
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::voxel::BlockType;
+
+    // twice the triangle area, bucketed by the direction the triangle faces
+    fn facing_areas(positions: &[IVec3], indices: &[u32]) -> [i64; 6] {
+        let mut areas = [0i64; 6];
+        for tri in indices.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| positions[tri[i] as usize].as_i64vec3());
+            let n = (b - a).cross(c - a);
+            let (area, bucket) = if n.x != 0 {
+                (n.x.abs(), (n.x > 0) as usize)
+            } else if n.y != 0 {
+                (n.y.abs(), 2 + (n.y > 0) as usize)
+            } else {
+                (n.z.abs(), 4 + (n.z > 0) as usize)
+            };
+            areas[bucket] += area;
+        }
+        areas
+    }
+
+    #[test]
+    fn collision_mesh_covers_render_mesh() {
+        let mut meshed = 0;
+        for seed in 0..16 {
+            let chunks_refs = ChunksRefs::make_dummy_chunk_refs(seed);
+            let Some(mesh) = build_chunk_mesh_with_collision(&chunks_refs, Lod::L32) else {
+                continue;
+            };
+            meshed += 1;
+            let collision = mesh.collision.expect("collision mesh was requested");
+
+            let render_positions: Vec<IVec3> = mesh
+                .vertices
+                .iter()
+                .map(|v| IVec3::new((v & 63) as i32, (v >> 6 & 63) as i32, (v >> 12 & 63) as i32))
+                .collect();
+            let collision_positions: Vec<IVec3> = collision
+                .positions
+                .iter()
+                .map(|p| Vec3::from_array(*p).as_ivec3())
+                .collect();
+
+            assert_eq!(
+                facing_areas(&render_positions, &mesh.indices),
+                facing_areas(&collision_positions, &collision.indices),
+                "seed {seed}"
+            );
+            assert!(collision.positions.len() <= mesh.vertices.len());
+        }
+        assert!(meshed > 0);
+    }
+
+    #[test]
+    fn collision_mesh_is_optional() {
+        let mesh = (0..16)
+            .find_map(|seed| build_chunk_mesh(&ChunksRefs::make_dummy_chunk_refs(seed), Lod::L32))
+            .expect("some seed produces a mesh");
+        assert!(mesh.collision.is_none());
+    }
+
+    #[test]
+    fn mesh_output_is_deterministic() {
+        for seed in 0..8 {
+            let chunks_refs = ChunksRefs::make_dummy_chunk_refs(seed);
+            // same voxels behind different allocations
+            let copy = ChunksRefs {
+                chunks: chunks_refs
+                    .chunks
+                    .iter()
+                    .map(|c| Arc::new(ChunkData::clone(c)))
+                    .collect(),
+            };
+            let mesh = |c| build_chunk_mesh(c, Lod::L32).map(|m| (m.vertices, m.indices));
+            assert_eq!(mesh(&chunks_refs), mesh(&copy), "seed {seed}");
+            let quads = |c| build_chunk_quads(c, Lod::L32, false).map(|m| m.quads);
+            assert_eq!(quads(&chunks_refs), quads(&copy), "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn quads_are_ordered_by_face_slice_and_block() {
+        let mut meshed = 0;
+        for seed in 0..8 {
+            let chunks_refs = ChunksRefs::make_dummy_chunk_refs(seed);
+            let Some(mesh) = build_chunk_quads(&chunks_refs, Lod::L32, false) else {
+                continue;
+            };
+            meshed += 1;
+            // normal index -> axis (down, up, left, right, forward, back)
+            let axis_of_normal = [2, 3, 0, 1, 4, 5];
+            let keys: Vec<_> = mesh
+                .quads
+                .iter()
+                .map(|q| {
+                    let normal = (q >> 32 & 7) as usize;
+                    let origin = [q & 63, q >> 6 & 63, q >> 12 & 63];
+                    // positive faces sit one past their slice
+                    let slice = origin[[0, 0, 1, 1, 2, 2][normal]] - (normal % 2) as u64;
+                    (axis_of_normal[normal], slice, q >> 35 & 127)
+                })
+                .collect();
+            assert!(keys.windows(2).all(|w| w[0] <= w[1]), "seed {seed}");
+        }
+        assert!(meshed > 0);
+    }
+
+    #[test]
+    fn plane_buckets_scale_to_many_block_types() {
+        let mut planes = PlaneBuckets::default();
+        for block in (0..1024u32).rev() {
+            planes.insert(3, 7, block << 9, block as usize % CHUNK_SIZE, 1);
+            planes.insert(3, 7, block << 9, 0, 0);
+        }
+        assert_eq!(planes.len(), 1024);
+        let mut keys = vec![];
+        planes.for_each(|face, slice, block_ao, plane| {
+            assert_eq!((face, slice), (3, 7));
+            let row = (block_ao >> 9) as usize % CHUNK_SIZE;
+            assert_eq!(plane[0] & 1, 1);
+            assert_eq!(plane[row] >> 1 & 1, 1);
+            keys.push(block_ao);
+        });
+        assert_eq!(keys, (0..1024).map(|b| b << 9).collect::<Vec<_>>());
+    }
+
+    fn add_voxel_to_axis_cols(
+        b: &crate::voxel::BlockData,
+        x: usize,
+        y: usize,
+        z: usize,
+        axis_cols: &mut AxisCols,
+    ) {
+        if b.block_type.is_solid() {
+            // x,z - y axis
+            axis_cols[0][z][x] |= 1u64 << y as u64;
+            // z,y - x axis
+            axis_cols[1][y][z] |= 1u64 << x as u64;
+            // x,y - z axis
+            axis_cols[2][y][x] |= 1u64 << z as u64;
+        }
+    }
+
+    // axis columns as they were built before add_padding_to_axis_cols, one get_block per voxel
+    fn reference_axis_cols(chunks_refs: &ChunksRefs) -> Box<AxisCols> {
+        let mut axis_cols = Box::new([[[0u64; CHUNK_SIZE_P]; CHUNK_SIZE_P]; 3]);
+        for z in 0..CHUNK_SIZE_P {
+            for y in 0..CHUNK_SIZE_P {
+                for x in 0..CHUNK_SIZE_P {
+                    let pos = ivec3(x as i32, y as i32, z as i32) - IVec3::ONE;
+                    add_voxel_to_axis_cols(chunks_refs.get_block(pos), x, y, z, &mut axis_cols);
+                }
+            }
+        }
+        axis_cols
+    }
+
+    fn fast_axis_cols(chunks_refs: &ChunksRefs) -> Box<AxisCols> {
+        let mut axis_cols = Box::new([[[0u64; CHUNK_SIZE_P]; CHUNK_SIZE_P]; 3]);
+        add_chunk_to_axis_cols(&chunks_refs.chunks[13], &mut axis_cols);
+        add_padding_to_axis_cols(chunks_refs, &mut axis_cols);
+        axis_cols
+    }
+
+    #[test]
+    fn padding_matches_get_block() {
+        for seed in 0..32 {
+            let chunks_refs = ChunksRefs::make_dummy_chunk_refs(seed);
+            assert!(
+                fast_axis_cols(&chunks_refs) == reference_axis_cols(&chunks_refs),
+                "seed {seed}"
+            );
+        }
+        // mix of uniform solid, uniform air and generated neighbours
+        let generated = ChunksRefs::make_dummy_chunk_refs(3);
+        for pattern in 0..8u32 {
+            let chunks_refs = ChunksRefs {
+                chunks: (0..27u32)
+                    .map(|i| match (i * 7 + pattern) % 3 {
+                        0 => Arc::new(ChunkData::filled(BlockType::Dirt)),
+                        1 => Arc::new(ChunkData::filled(BlockType::Water)),
+                        _ => generated.chunks[i as usize].clone(),
+                    })
+                    .collect(),
+            };
+            assert!(
+                fast_axis_cols(&chunks_refs) == reference_axis_cols(&chunks_refs),
+                "pattern {pattern}"
+            );
+        }
+    }
+
+    // ambient occlusion as it was sampled before ao_sample_cols, one get_block per sample
+    fn reference_ao_index(chunks_refs: &ChunksRefs, axis: usize, voxel_pos: IVec3) -> u32 {
+        let mut ao_index = 0;
+        for (ao_i, ao_offset) in ADJACENT_AO_DIRS.iter().enumerate() {
+            let ao_sample_offset = match axis {
+                0 => ivec3(ao_offset.x, -1, ao_offset.y), // down
+                1 => ivec3(ao_offset.x, 1, ao_offset.y),  // up
+                2 => ivec3(-1, ao_offset.y, ao_offset.x), // left
+                3 => ivec3(1, ao_offset.y, ao_offset.x),  // right
+                4 => ivec3(ao_offset.x, ao_offset.y, -1), // forward
+                _ => ivec3(ao_offset.x, ao_offset.y, 1),  // back
+            };
+            let ao_block = chunks_refs.get_block(voxel_pos + ao_sample_offset);
+            if ao_block.block_type.is_solid() {
+                ao_index |= 1u32 << ao_i;
+            }
+        }
+        ao_index
+    }
+
+    #[test]
+    fn bitmask_ao_matches_get_block() {
+        let mut occluded = 0;
+        for seed in 0..16 {
+            let chunks_refs = ChunksRefs::make_dummy_chunk_refs(seed);
+            let axis_cols = fast_axis_cols(&chunks_refs);
+            // every voxel, not only the visible faces
+            for axis in 0..6 {
+                for z in 0..CHUNK_SIZE {
+                    for x in 0..CHUNK_SIZE {
+                        let ao_cols = ao_sample_cols(&axis_cols, axis, z, x);
+                        for y in 0..CHUNK_SIZE as u32 {
+                            let voxel_pos = match axis {
+                                0 | 1 => ivec3(x as i32, y as i32, z as i32),
+                                2 | 3 => ivec3(y as i32, z as i32, x as i32),
+                                _ => ivec3(x as i32, z as i32, y as i32),
+                            };
+                            let ao_index = ao_index_at(&ao_cols, y);
+                            assert_eq!(
+                                ao_index,
+                                reference_ao_index(&chunks_refs, axis, voxel_pos),
+                                "seed {seed} axis {axis} {voxel_pos}"
+                            );
+                            occluded += (ao_index != 0 && ao_index != 511) as usize;
+                        }
+                    }
+                }
+            }
+        }
+        assert!(occluded > 0);
+    }
+
+    #[test]
+    fn solid_chunk_uses_every_column_bit() {
+        // a solid chunk surrounded by air, the padding bits sit at the very ends of the u64 columns
+        let chunks_refs = ChunksRefs::with_middle(ChunkData::filled(BlockType::Dirt));
+        let mesh = build_chunk_mesh(&chunks_refs, Lod::L32).unwrap();
+        // one full quad for every face
+        assert_eq!(mesh.vertices.len(), 6 * 4);
+        let size = CHUNK_SIZE as u32;
+        for v in mesh.vertices.iter() {
+            let pos = [v & 63, v >> 6 & 63, v >> 12 & 63];
+            assert!(pos.iter().all(|p| *p == 0 || *p == size), "{pos:?}");
+        }
+    }
+
+    // rows mixing noise with empty, full and solid runs, so quads grow across rows
+    fn plane_row() -> impl Strategy<Value = u64> {
+        prop_oneof![
+            any::<u64>(),
+            Just(0),
+            Just(!0),
+            (0..64u32, 0..64u32)
+                .prop_map(|(a, b)| range_mask(a.min(b) as usize, a.max(b) as usize)),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn binary_plane_quads_tile_set_bits(
+            rows in prop::collection::vec(plane_row(), CHUNK_SIZE),
+            lod_size in 0..=CHUNK_SIZE as u32,
+        ) {
+            let data: [PlaneRow; CHUNK_SIZE] = std::array::from_fn(|i| rows[i] as PlaneRow);
+            let quads = greedy_mesh_binary_plane(data, lod_size);
+            let rows = data.map(|row| row as u64);
+            prop_assert_eq!(check_plane_tiling(&rows, lod_size, &quads), Ok(()));
+        }
+
+        #[test]
+        fn binary_plane_u64_quads_tile_set_bits(
+            rows in prop::collection::vec(plane_row(), 64),
+            lod_size in 0..=80u32,
+        ) {
+            let data: [u64; 64] = rows.try_into().unwrap();
+            let quads = greedy_mesh_binary_plane_u64(data, lod_size);
+            prop_assert_eq!(check_plane_tiling(&data, lod_size, &quads), Ok(()));
+        }
+    }
+
+    #[test]
+    fn binary_plane_ignores_bits_outside_lod_size() {
+        let quads = greedy_mesh_binary_plane_u64([!0u64; 8], 4);
+        assert_eq!(quads.len(), 1);
+        assert_eq!(
+            (quads[0].x, quads[0].y, quads[0].w, quads[0].h),
+            (0, 0, 4, 4)
+        );
+        assert!(greedy_mesh_binary_plane_u64([!0u64; 8], 0).is_empty());
+    }
+
+    #[test]
+    fn tiling_check_rejects_bad_quads() {
+        let rows = [0b0110u64, 0b0110];
+        let quad = |x, y, w, h| GreedyQuad { x, y, w, h };
+        assert_eq!(check_plane_tiling(&rows, 4, &[quad(0, 1, 2, 2)]), Ok(()));
+        // missing, overlapping, unset and out of bounds
+        assert!(check_plane_tiling(&rows, 4, &[quad(0, 1, 1, 2)]).is_err());
+        assert!(check_plane_tiling(&rows, 4, &[quad(0, 1, 2, 2), quad(1, 1, 1, 1)]).is_err());
+        assert!(check_plane_tiling(&rows, 4, &[quad(0, 0, 2, 3)]).is_err());
+        assert!(check_plane_tiling(&rows, 2, &[quad(0, 1, 2, 2)]).is_err());
+    }
+}
@@ -1,17 +1,30 @@
+pub mod block_tick;
+pub mod character_controller;
 pub mod chunk;
 pub mod chunk_mesh;
 pub mod chunks_refs;
+pub mod collision;
 pub mod constants;
 pub mod culled_mesher;
 pub mod culled_mesher_optimized;
 pub mod face_direction;
+pub mod fluid;
+pub mod gravity;
 pub mod greedy_mesher;
 pub mod greedy_mesher_optimized;
+pub mod load_area;
 pub mod lod;
+pub mod mesher;
+pub mod packed_quad;
+pub mod pathfinding;
 pub mod quad;
+pub mod random_tick;
 pub mod rendering;
 pub mod scanner;
+pub mod smooth_mesher;
 pub mod sun;
+#[cfg(test)]
+mod test_utils;
 pub mod utils;
 pub mod voxel;
 pub mod voxel_engine;
@@ -0,0 +1,290 @@
+use std::{fmt, sync::Arc};
+
+use bevy::prelude::*;
+
+/// shape of the region a scanner keeps loaded, scaled by the scanner's distance
+#[derive(Clone, Default)]
+pub enum LoadShape {
+    /// every chunk within distance along each axis
+    #[default]
+    Cube,
+    /// every chunk within distance of the center
+    Sphere,
+    /// distance around the vertical axis, with its own vertical radius in chunks
+    Cylinder { vertical: i32 },
+    /// called with the offset from the center chunk and the distance,
+    /// only offsets within distance along each axis are ever considered
+    Custom(Arc<dyn Fn(IVec3, i32) -> bool + Send + Sync>),
+}
+
+impl LoadShape {
+    pub fn custom(f: impl Fn(IVec3, i32) -> bool + Send + Sync + 'static) -> Self {
+        Self::Custom(Arc::new(f))
+    }
+}
+
+impl PartialEq for LoadShape {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Cube, Self::Cube) | (Self::Sphere, Self::Sphere) => true,
+            (Self::Cylinder { vertical: a }, Self::Cylinder { vertical: b }) => a == b,
+            (Self::Custom(a), Self::Custom(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Debug for LoadShape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cube => write!(f, "Cube"),
+            Self::Sphere => write!(f, "Sphere"),
+            Self::Cylinder { vertical } => write!(f, "Cylinder {{ vertical: {vertical} }}"),
+            Self::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+// largest w with w * w <= v
+fn isqrt(v: i32) -> Option<i32> {
+    if v < 0 {
+        return None;
+    }
+    let mut w = (v as f64).sqrt() as i32;
+    while w * w > v {
+        w -= 1;
+    }
+    while (w + 1) * (w + 1) <= v {
+        w += 1;
+    }
+    Some(w)
+}
+
+/// chunks covered by a load shape around center
+/// margin grows the area by that many chunks in every direction (including diagonals),
+/// the data area uses a margin of 1 so every meshed chunk has all of its neighbours
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChunkArea {
+    pub center: IVec3,
+    pub half: i32,
+    pub shape: LoadShape,
+    pub margin: i32,
+}
+
+impl ChunkArea {
+    /// cube of chunks within half chunks of center, along every axis
+    pub fn new(center: IVec3, half: i32) -> Self {
+        Self::with_shape(center, half, LoadShape::Cube)
+    }
+
+    pub fn with_shape(center: IVec3, half: i32, shape: LoadShape) -> Self {
+        Self {
+            center,
+            half,
+            shape,
+            margin: 0,
+        }
+    }
+
+    pub fn with_margin(mut self, margin: i32) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    // half width of the shape's row at an offset from the center, margin excluded
+    fn shape_row_half_width(&self, dy: i32, dz: i32) -> Option<i32> {
+        let h = self.half;
+        // + h rounds the radius up by half a chunk, so axis neighbours of the rim are included
+        let r2 = h * h + h;
+        match &self.shape {
+            LoadShape::Cube => (dy.abs() <= h && dz.abs() <= h).then_some(h),
+            LoadShape::Sphere => isqrt(r2 - dy * dy - dz * dz),
+            LoadShape::Cylinder { vertical } => match dy.abs() <= *vertical {
+                true => isqrt(r2 - dz * dz),
+                false => None,
+            },
+            LoadShape::Custom(_) => None,
+        }
+    }
+
+    // inclusive x span of the row at y, z, an empty row has min > max.
+    // None for custom shapes, those are tested chunk by chunk
+    fn row_span(&self, y: i32, z: i32) -> Option<(i32, i32)> {
+        if matches!(self.shape, LoadShape::Custom(_)) {
+            return None;
+        }
+        let (c, m) = (self.center, self.margin);
+        // every row shape is centered on center.x, so the widest nearby row covers the others
+        let width = (-m..=m)
+            .flat_map(|a| (-m..=m).map(move |b| (a, b)))
+            .filter_map(|(a, b)| self.shape_row_half_width(y - c.y + a, z - c.z + b))
+            .max();
+        Some(match width {
+            Some(w) => (c.x - w - m, c.x + w + m),
+            None => (c.x, c.x - 1),
+        })
+    }
+
+    pub fn contains(&self, chunk_pos: IVec3) -> bool {
+        if let Some((min_x, max_x)) = self.row_span(chunk_pos.y, chunk_pos.z) {
+            return (min_x..=max_x).contains(&chunk_pos.x);
+        }
+        let LoadShape::Custom(f) = &self.shape else {
+            unreachable!()
+        };
+        let (h, m) = (self.half, self.margin);
+        let offset = chunk_pos - self.center;
+        (-m..=m).any(|z| {
+            (-m..=m).any(|y| {
+                (-m..=m).any(|x| {
+                    let o = offset + IVec3::new(x, y, z);
+                    o.abs().max_element() <= h && f(o, h)
+                })
+            })
+        })
+    }
+
+    // bounding box half extents around center
+    fn extents(&self) -> IVec3 {
+        let vertical = match self.shape {
+            LoadShape::Cylinder { vertical } => vertical,
+            _ => self.half,
+        };
+        IVec3::new(self.half, vertical, self.half) + self.margin
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = IVec3> {
+        self.difference(None).into_iter()
+    }
+
+    /// chunks inside self but not inside other
+    /// rows of the builtin shapes are clipped as spans, custom shapes are tested per chunk
+    pub fn difference(&self, other: Option<&ChunkArea>) -> Vec<IVec3> {
+        let (c, e) = (self.center, self.extents());
+        let mut result = vec![];
+        for z in c.z - e.z..=c.z + e.z {
+            for y in c.y - e.y..=c.y + e.y {
+                let other_span = match other {
+                    Some(other) => other.row_span(y, z).map(Some),
+                    None => Some(None),
+                };
+                match (self.row_span(y, z), other_span) {
+                    (Some((min_x, max_x)), Some(Some((o_min, o_max)))) => {
+                        let left = min_x..=max_x.min(o_min - 1);
+                        let right = min_x.max(o_max + 1)..=max_x;
+                        result.extend(left.chain(right).map(|x| IVec3::new(x, y, z)));
+                    }
+                    (Some((min_x, max_x)), Some(None)) => {
+                        result.extend((min_x..=max_x).map(|x| IVec3::new(x, y, z)));
+                    }
+                    _ => {
+                        result.extend(
+                            (c.x - e.x..=c.x + e.x)
+                                .map(|x| IVec3::new(x, y, z))
+                                .filter(|p| self.contains(*p))
+                                .filter(|p| other.is_none_or(|o| !o.contains(*p))),
+                        );
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::ADJACENT_CHUNK_DIRECTIONS;
+    use bevy::utils::HashSet;
+
+    // brute force reference, every chunk in a generous box tested with contains
+    fn brute_force(area: &ChunkArea) -> HashSet<IVec3> {
+        let r = area.half + area.margin + 4;
+        let mut set = HashSet::new();
+        for z in -r..=r {
+            for y in -r..=r {
+                for x in -r..=r {
+                    let p = area.center + IVec3::new(x, y, z);
+                    if area.contains(p) {
+                        set.insert(p);
+                    }
+                }
+            }
+        }
+        set
+    }
+
+    fn test_areas() -> Vec<ChunkArea> {
+        let diamond = LoadShape::custom(|o, r| o.abs().x + o.abs().y + o.abs().z <= r);
+        let mut areas = vec![];
+        for (center, half) in [
+            (IVec3::ZERO, 3),
+            (IVec3::new(1, 0, 0), 3),
+            (IVec3::new(-2, 1, 3), 4),
+            (IVec3::new(20, 0, 0), 2),
+            (IVec3::new(4, -4, 4), 0),
+        ] {
+            for shape in [
+                LoadShape::Cube,
+                LoadShape::Sphere,
+                LoadShape::Cylinder { vertical: 1 },
+                diamond.clone(),
+            ] {
+                for margin in [0, 1] {
+                    areas.push(
+                        ChunkArea::with_shape(center, half, shape.clone()).with_margin(margin),
+                    );
+                }
+            }
+        }
+        areas
+    }
+
+    #[test]
+    fn area_difference_matches_sets() {
+        let areas = test_areas();
+        for a in areas.iter() {
+            let a_set = brute_force(a);
+            assert_eq!(a.iter().collect::<HashSet<_>>(), a_set, "{a:?}");
+            for b in areas.iter().step_by(3) {
+                let b_set = brute_force(b);
+                let expected = a_set.difference(&b_set).copied().collect::<HashSet<_>>();
+                let diff = a.difference(Some(b));
+                assert_eq!(diff.len(), expected.len(), "{a:?} {b:?}");
+                assert_eq!(diff.into_iter().collect::<HashSet<_>>(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn cube_area_matches_distance() {
+        let area = ChunkArea::new(IVec3::ZERO, 2);
+        assert_eq!(area.iter().count(), 125);
+        assert_eq!(area.with_margin(1).iter().count(), 343);
+    }
+
+    #[test]
+    fn shapes_are_bounded() {
+        let sphere = ChunkArea::with_shape(IVec3::ZERO, 8, LoadShape::Sphere);
+        assert!(sphere.contains(IVec3::new(8, 0, 0)));
+        assert!(!sphere.contains(IVec3::new(6, 6, 1)));
+
+        let cylinder = ChunkArea::with_shape(IVec3::ZERO, 8, LoadShape::Cylinder { vertical: 2 });
+        assert!(cylinder.contains(IVec3::new(0, 2, 8)));
+        assert!(!cylinder.contains(IVec3::new(0, 3, 0)));
+        assert!(cylinder.iter().all(|p| p.y.abs() <= 2));
+    }
+
+    #[test]
+    fn margin_covers_mesh_neighbours() {
+        for area in test_areas() {
+            let data = area.clone().with_margin(area.margin + 1);
+            for p in area.iter() {
+                for d in ADJACENT_CHUNK_DIRECTIONS {
+                    assert!(data.contains(p + d), "{area:?} {p}");
+                }
+            }
+        }
+    }
+}
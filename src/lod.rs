@@ -1,3 +1,5 @@
+use crate::constants::CHUNK_SIZE_I32;
+
 ///! level of detail
 #[derive(Copy, Clone)]
 pub enum Lod {
@@ -11,13 +13,7 @@ pub enum Lod {
 impl Lod {
     ///! the amount of voxels per axis
     pub fn size(&self) -> i32 {
-        match self {
-            Lod::L32 => 32,
-            Lod::L16 => 16,
-            Lod::L8 => 8,
-            Lod::L4 => 4,
-            Lod::L2 => 2,
-        }
+        CHUNK_SIZE_I32 / self.jump_index()
     }
 
     ///! how much to multiply to reach next voxel
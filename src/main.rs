@@ -17,13 +17,20 @@ use bevy_screen_diagnostics::{
 };
 
 use new_voxel_testing::{
+    character_controller::{CharacterController, CharacterControllerPlugin},
+    constants::{CHUNK_SIZE2, CHUNK_SIZE_I32},
+    fluid::FluidPlugin,
+    gravity::GravityPlugin,
+    load_area::LoadShape,
+    random_tick::{spread_grass, RandomTickHandlers, RandomTickPlugin},
     rendering::{
-        ChunkMaterial, ChunkMaterialWireframe, GlobalChunkMaterial, GlobalChunkWireframeMaterial,
+        ChunkMaterial, ChunkMaterialWireframe, ChunkQuadMaterial, GlobalChunkMaterial,
+        GlobalChunkQuadMaterial, GlobalChunkWireframeMaterial, GlobalSmoothChunkMaterial,
         RenderingPlugin,
     },
     scanner::{Scanner, ScannerPlugin},
     sun::{Sun, SunPlugin},
-    utils::world_to_chunk,
+    utils::{world_to_chunk, world_voxel_to_chunk},
     voxel::*,
     voxel_engine::{ChunkModification, VoxelEngine, VoxelEnginePlugin},
 };
@@ -57,6 +64,11 @@ fn main() {
         .add_plugins(VoxelEnginePlugin)
         .add_plugins(SunPlugin)
         .add_plugins(ScannerPlugin)
+        .add_plugins(CharacterControllerPlugin)
+        .add_plugins(FluidPlugin)
+        .add_plugins(GravityPlugin)
+        .add_plugins(RandomTickPlugin)
+        .add_systems(Startup, register_random_ticks)
         .add_systems(Startup, setup)
         // camera plugin
         .add_plugins(NoCameraPlayerPlugin)
@@ -72,9 +84,85 @@ fn main() {
                                   // speed: 32.0 * 12.0,   // default: 12.0
         })
         .add_systems(Update, modify_current_terrain)
+        .add_systems(Update, toggle_walking)
+        .add_systems(Update, place_block)
+        .add_systems(Update, adjust_view_distance)
         .run();
 }
 
+pub fn register_random_ticks(mut handlers: ResMut<RandomTickHandlers>) {
+    handlers.register(BlockType::Grass, spread_grass);
+}
+
+// grow (=) or shrink (-) the view distance of every scanner
+pub fn adjust_view_distance(mut scanners: Query<&mut Scanner>, key: Res<ButtonInput<KeyCode>>) {
+    let change = if key.just_pressed(KeyCode::Equal) {
+        1
+    } else if key.just_pressed(KeyCode::Minus) {
+        -1
+    } else {
+        return;
+    };
+    for mut scanner in scanners.iter_mut() {
+        scanner.distance = (scanner.distance + change).clamp(1, 64);
+        info!("view distance: {}", scanner.distance);
+    }
+}
+
+// swap between flying and walking, the flycam still handles looking around
+pub fn toggle_walking(
+    mut commands: Commands,
+    query: Query<(Entity, Has<CharacterController>), With<FlyCam>>,
+    key: Res<ButtonInput<KeyCode>>,
+    mut movement_settings: ResMut<MovementSettings>,
+    mut fly_speed: Local<f32>,
+) {
+    if !key.just_pressed(KeyCode::KeyG) {
+        return;
+    }
+    for (entity, walking) in query.iter() {
+        if walking {
+            commands.entity(entity).remove::<CharacterController>();
+            movement_settings.speed = *fly_speed;
+        } else {
+            commands
+                .entity(entity)
+                .insert(CharacterController::default());
+            *fly_speed = movement_settings.speed;
+            movement_settings.speed = 0.0;
+        }
+    }
+}
+
+// place water (F), lava (L), sand (B) or gravel (V) a few voxels in front of the camera
+pub fn place_block(
+    query: Query<&Transform, With<Camera>>,
+    key: Res<ButtonInput<KeyCode>>,
+    mut voxel_engine: ResMut<VoxelEngine>,
+) {
+    let block_type = if key.just_pressed(KeyCode::KeyF) {
+        BlockType::Water
+    } else if key.just_pressed(KeyCode::KeyL) {
+        BlockType::Lava
+    } else if key.just_pressed(KeyCode::KeyB) {
+        BlockType::Sand
+    } else if key.just_pressed(KeyCode::KeyV) {
+        BlockType::Gravel
+    } else {
+        return;
+    };
+    let cam_transform = query.single();
+    let target = (cam_transform.translation + (cam_transform.forward() * 8.0))
+        .floor()
+        .as_ivec3();
+    let (chunk_pos, local_pos) = world_voxel_to_chunk(target);
+    voxel_engine
+        .chunk_modifications
+        .entry(chunk_pos)
+        .or_default()
+        .push(ChunkModification(local_pos, block_type.into()));
+}
+
 pub fn modify_current_terrain(
     query: Query<&Transform, With<Camera>>,
     key: Res<ButtonInput<KeyCode>>,
@@ -88,13 +176,13 @@ pub fn modify_current_terrain(
 
     let mut rng = rand::thread_rng();
     let mut mods = vec![];
-    for _i in 0..32 * 32 {
+    for _i in 0..CHUNK_SIZE2 {
         let pos = ivec3(
-            rng.gen_range(0..32),
-            rng.gen_range(0..32),
-            rng.gen_range(0..32),
+            rng.gen_range(0..CHUNK_SIZE_I32),
+            rng.gen_range(0..CHUNK_SIZE_I32),
+            rng.gen_range(0..CHUNK_SIZE_I32),
         );
-        mods.push(ChunkModification(pos, BlockType::Air));
+        mods.push(ChunkModification(pos, BlockType::Air.into()));
     }
     voxel_engine.chunk_modifications.insert(cam_chunk, mods);
 }
@@ -138,7 +226,8 @@ pub fn setup(
 
     commands
         .spawn((
-            Scanner::new(12),
+            // terrain is mostly flat, so far fewer chunks are needed vertically
+            Scanner::new(12).with_shape(LoadShape::Cylinder { vertical: 6 }),
             Camera3dBundle {
                 transform: Transform::from_xyz(0.0, 2.0, 0.5),
                 ..default()
@@ -158,6 +247,18 @@ pub fn setup(
             metallic: 0.01,
         },
     )));
+    commands.insert_resource(GlobalChunkQuadMaterial(ChunkQuadMaterial {
+        reflectance: 0.5,
+        perceptual_roughness: 1.0,
+        metallic: 0.01,
+        quads: vec![],
+    }));
+    commands.insert_resource(GlobalSmoothChunkMaterial(materials.add(StandardMaterial {
+        reflectance: 0.5,
+        perceptual_roughness: 1.0,
+        metallic: 0.01,
+        ..default()
+    })));
 
     // circular base in origin
     commands.spawn(PbrBundle {
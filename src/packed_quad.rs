@@ -0,0 +1,224 @@
+use bevy::math::IVec3;
+
+use crate::{
+    constants::CHUNK_SIZE,
+    utils::{make_fluid_vertex_u32, make_vertex_u32},
+};
+
+// one greedy quad packed into a u64, expanded into 6 vertices by the vertex shader
+// (see assets/shaders/chunk.wgsl, PACKED_QUADS). the gpu reads it as a vec2<u32>,
+// so no field crosses the 32 bit boundary.
+//
+// low:  x 6b | y 6b | z 6b | w 6b | h 6b | unused 2b
+// high: normal 3b | block 7b | ao 4x2b | rotate 1b | fluid 1b | fluid drop 3b
+const W_SHIFT: u64 = 18;
+const H_SHIFT: u64 = 24;
+const NORMAL_SHIFT: u64 = 32;
+const BLOCK_SHIFT: u64 = 35;
+const AO_SHIFT: u64 = 42;
+const ROTATE_SHIFT: u64 = 50;
+const FLUID_SHIFT: u64 = 51;
+const DROP_SHIFT: u64 = 52;
+
+// x, y, z, w and h all reach CHUNK_SIZE, and get the same number of bits
+const _: () = assert!(CHUNK_SIZE < 1 << (W_SHIFT / 3));
+const _: () = assert!(CHUNK_SIZE < 1 << (H_SHIFT - W_SHIFT));
+
+/// corner of the quad used by each of its 6 vertices, same as utils::generate_indices
+pub const QUAD_VERTEX_CORNERS: [usize; 6] = [0, 1, 2, 0, 2, 3];
+
+/// pack a quad spanning w,h from its minimum corner
+/// corner_ao is the ambient occlusion (0-3) of the corners in order
+/// (0,0), (w,0), (w,h), (0,h) along the face's quad_axes.
+/// rotate shifts the triangle diagonal, like the anisotropy flip in greedy_mesher_optimized
+pub fn pack_quad(
+    origin: IVec3,
+    w: u32,
+    h: u32,
+    normal: u32,
+    block_type: u32,
+    corner_ao: [u32; 4],
+    rotate: bool,
+) -> u64 {
+    let ao = corner_ao
+        .iter()
+        .enumerate()
+        .fold(0u64, |ao, (i, a)| ao | (*a as u64) << (i * 2));
+    origin.x as u64
+        | (origin.y as u64) << 6
+        | (origin.z as u64) << 12
+        | (w as u64) << W_SHIFT
+        | (h as u64) << H_SHIFT
+        | (normal as u64) << NORMAL_SHIFT
+        | (block_type as u64) << BLOCK_SHIFT
+        | ao << AO_SHIFT
+        | (rotate as u64) << ROTATE_SHIFT
+}
+
+/// pack a single voxel fluid face, corners above the voxel are lowered by drop (in 1/8ths)
+/// positive faces are rotated to keep the triangle order of quad::Quad::from_direction
+pub fn pack_fluid_quad(origin: IVec3, normal: u32, block_type: u32, drop: u32) -> u64 {
+    pack_quad(origin, 1, 1, normal, block_type, [0; 4], normal % 2 == 1)
+        | 1u64 << FLUID_SHIFT
+        | (drop as u64) << DROP_SHIFT
+}
+
+/// axes the quad's width and height extend along, based on the normal index
+#[inline]
+pub fn quad_axes(normal: u32) -> (IVec3, IVec3) {
+    match normal {
+        0 | 1 => (IVec3::Z, IVec3::Y), // left, right
+        2 | 3 => (IVec3::X, IVec3::Z), // down, up
+        _ => (IVec3::X, IVec3::Y),     // forward, back
+    }
+}
+
+/// cpu reference of the vertex shader, the u32 vertex (utils::make_vertex_u32)
+/// of the quad's vertex_index'th vertex (0-5)
+pub fn quad_vertex(quad: u64, vertex_index: u32) -> u32 {
+    let bits = |shift: u64, len: u64| ((quad >> shift) & ((1 << len) - 1)) as u32;
+    let origin = IVec3::new(bits(0, 6) as i32, bits(6, 6) as i32, bits(12, 6) as i32);
+    let (w, h) = (bits(W_SHIFT, 6) as i32, bits(H_SHIFT, 6) as i32);
+    let normal = bits(NORMAL_SHIFT, 3);
+    let block_type = bits(BLOCK_SHIFT, 7);
+
+    // positive faces (and forward) have their corners wound in reverse,
+    // see FaceDir::reverse_order
+    let k = (QUAD_VERTEX_CORNERS[vertex_index as usize % 6] + bits(ROTATE_SHIFT, 1) as usize) % 4;
+    let corner = match matches!(normal, 1 | 3 | 4) {
+        true => (4 - k) % 4,
+        false => k,
+    };
+    let (u, v) = quad_axes(normal);
+    let pos = match corner {
+        0 => origin,
+        1 => origin + u * w,
+        2 => origin + u * w + v * h,
+        _ => origin + v * h,
+    };
+
+    if bits(FLUID_SHIFT, 1) == 1 {
+        // the voxel sits below the quad for up faces
+        let voxel_y = origin.y - (normal == 3) as i32;
+        let drop = match pos.y > voxel_y {
+            true => bits(DROP_SHIFT, 3),
+            false => 0,
+        };
+        make_fluid_vertex_u32(pos, drop, normal, block_type)
+    } else {
+        let ao = bits(AO_SHIFT + corner as u64 * 2, 2);
+        make_vertex_u32(pos, ao, normal, block_type)
+    }
+}
+
+/// the 4 vertices of a quad, in the order the current u32 vertex layout uses
+pub fn quad_vertices(quad: u64) -> [u32; 4] {
+    [0, 1, 2, 5].map(|i| quad_vertex(quad, i))
+}
+
+/// expand packed quads into the u32 vertex layout, indices from utils::generate_indices fit
+pub fn unpack_quads(quads: &[u64]) -> Vec<u32> {
+    quads.iter().flat_map(|q| quad_vertices(*q)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chunk::ChunkData,
+        chunks_refs::ChunksRefs,
+        constants::{CHUNK_SIZE3, CHUNK_SIZE_I32},
+        fluid::{append_fluid_quads, append_fluid_vertices},
+        greedy_mesher_optimized::{build_chunk_mesh, build_chunk_quads},
+        lod::Lod,
+        utils::{generate_indices, vec3_to_index},
+        voxel::{BlockData, BlockType},
+    };
+
+    // the triangles drawn by the vertex shader, 6 vertices per quad
+    fn pulled_triangles(quads: &[u64]) -> Vec<u32> {
+        quads
+            .iter()
+            .flat_map(|q| (0..6).map(|i| quad_vertex(*q, i)))
+            .collect()
+    }
+
+    // the triangles drawn from u32 vertices and indices
+    fn indexed_triangles(vertices: &[u32], indices: &[u32]) -> Vec<u32> {
+        indices.iter().map(|i| vertices[*i as usize]).collect()
+    }
+
+    #[test]
+    fn packed_quads_match_greedy_vertices() {
+        let mut meshed = 0;
+        for seed in 0..32 {
+            let chunks_refs = ChunksRefs::make_dummy_chunk_refs(seed);
+            let mesh = build_chunk_mesh(&chunks_refs, Lod::L32);
+            let quads = build_chunk_quads(&chunks_refs, Lod::L32, false);
+            assert_eq!(mesh.is_some(), quads.is_some(), "seed {seed}");
+            let (Some(mesh), Some(quads)) = (mesh, quads) else {
+                continue;
+            };
+            meshed += 1;
+            assert!(quads.vertices.is_empty() && quads.indices.is_empty());
+            assert_eq!(mesh.vertices, unpack_quads(&quads.quads), "seed {seed}");
+            assert_eq!(
+                indexed_triangles(&mesh.vertices, &mesh.indices),
+                pulled_triangles(&quads.quads),
+                "seed {seed}"
+            );
+            // 8 bytes per quad instead of 4 vertices and 6 indices
+            assert!(quads.quads.len() * 8 < (mesh.vertices.len() + mesh.indices.len()) * 4);
+        }
+        assert!(meshed > 0);
+    }
+
+    #[test]
+    fn packed_fluid_quads_match_fluid_vertices() {
+        let mut voxels = vec![BlockData::default(); CHUNK_SIZE3];
+        let mut set =
+            |x, y, z, block| voxels[vec3_to_index(IVec3::new(x, y, z), CHUNK_SIZE_I32)] = block;
+        set(4, 4, 4, BlockData::fluid(BlockType::Water, 5));
+        set(5, 4, 4, BlockData::fluid(BlockType::Water, 2));
+        set(4, 5, 4, BlockData::fluid(BlockType::Water, 7));
+        set(8, 0, 8, BlockData::fluid(BlockType::Lava, 3));
+        set(8, 1, 8, BlockData::from(BlockType::Dirt));
+        let chunks_refs = ChunksRefs::with_middle(ChunkData::new(voxels));
+
+        let mut vertices = vec![];
+        append_fluid_vertices(&chunks_refs, &mut vertices);
+        let mut quads = vec![];
+        append_fluid_quads(&chunks_refs, &mut quads);
+        assert_eq!(vertices.len(), quads.len() * 4);
+        assert_eq!(vertices, unpack_quads(&quads));
+        assert_eq!(
+            indexed_triangles(&vertices, &generate_indices(vertices.len())),
+            pulled_triangles(&quads)
+        );
+    }
+
+    #[test]
+    fn pack_quad_round_trips_fields() {
+        let size = CHUNK_SIZE_I32 as u32;
+        let quad = pack_quad(
+            IVec3::new(0, CHUNK_SIZE_I32, 3),
+            size,
+            1,
+            3,
+            127,
+            [3, 0, 1, 2],
+            false,
+        );
+        // up faces are wound in reverse
+        let [v1, v4, v3, v2] = quad_vertices(quad);
+        let pos = |v: u32| [v & 63, v >> 6 & 63, v >> 12 & 63];
+        assert_eq!(pos(v1), [0, size, 3]);
+        assert_eq!(pos(v2), [size, size, 3]);
+        assert_eq!(pos(v3), [size, size, 4]);
+        assert_eq!(pos(v4), [0, size, 4]);
+        assert_eq!([v1, v2, v3, v4].map(|v| v >> 18 & 7), [3, 0, 1, 2]);
+        assert!([v1, v2, v3, v4]
+            .iter()
+            .all(|v| v >> 21 & 7 == 3 && v >> 25 == 127));
+    }
+}
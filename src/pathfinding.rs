@@ -0,0 +1,357 @@
+use std::{cmp::Reverse, collections::BinaryHeap, sync::Arc};
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    chunk::ChunkData,
+    voxel_engine::{get_world_block, is_world_voxel_solid},
+};
+
+// cost of a single horizontal step, vertical movement is added on top
+const STEP_COST: u32 = 10;
+const VERTICAL_COST: u32 = 4;
+
+const HORIZONTAL_DIRS: [IVec3; 4] = [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z];
+
+/// describes how an agent is able to move between voxels
+#[derive(Copy, Clone, Debug)]
+pub struct PathSettings {
+    /// passable voxels the agent needs, counted upwards from its feet
+    pub headroom: i32,
+    /// highest ledge the agent can climb in one step
+    pub max_step_up: i32,
+    /// deepest drop the agent is willing to walk off
+    pub max_drop: i32,
+    /// give up after expanding this many voxels
+    pub max_visited: usize,
+}
+
+impl Default for PathSettings {
+    fn default() -> Self {
+        Self {
+            headroom: 2,
+            max_step_up: 1,
+            max_drop: 3,
+            max_visited: 50_000,
+        }
+    }
+}
+
+// voxels in unloaded chunks are neither passable nor solid, agents never path through them
+fn is_passable(world_data: &HashMap<IVec3, Arc<ChunkData>>, pos: IVec3) -> bool {
+    get_world_block(world_data, pos).is_some_and(|b| b.block_type.is_passable())
+}
+
+fn is_solid(world_data: &HashMap<IVec3, Arc<ChunkData>>, pos: IVec3) -> bool {
+    is_world_voxel_solid(world_data, pos).unwrap_or(false)
+}
+
+// inclusive column of passable voxels from y_min to y_max
+fn is_passable_column(
+    world_data: &HashMap<IVec3, Arc<ChunkData>>,
+    pos: IVec3,
+    y_min: i32,
+    y_max: i32,
+) -> bool {
+    (y_min..=y_max).all(|y| is_passable(world_data, IVec3::new(pos.x, y, pos.z)))
+}
+
+/// a voxel is walkable if it's passable, standing on a solid voxel, with enough headroom
+pub fn is_walkable(
+    world_data: &HashMap<IVec3, Arc<ChunkData>>,
+    pos: IVec3,
+    settings: &PathSettings,
+) -> bool {
+    is_solid(world_data, pos - IVec3::Y)
+        && is_passable_column(world_data, pos, pos.y, pos.y + settings.headroom - 1)
+}
+
+/// walkable voxels reachable in a single step from pos
+fn neighbours(
+    world_data: &HashMap<IVec3, Arc<ChunkData>>,
+    pos: IVec3,
+    settings: PathSettings,
+) -> impl Iterator<Item = IVec3> + '_ {
+    HORIZONTAL_DIRS.into_iter().flat_map(move |dir| {
+        (-settings.max_drop..=settings.max_step_up).filter_map(move |dy| {
+            let next = pos + dir + IVec3::Y * dy;
+            if !is_walkable(world_data, next, &settings) {
+                return None;
+            }
+            let clear = match dy {
+                // room to climb up, before moving over the ledge
+                1.. => {
+                    is_passable_column(world_data, pos, pos.y, pos.y + dy + settings.headroom - 1)
+                }
+                // room to walk over the edge, before dropping down
+                ..=-1 => {
+                    is_passable_column(world_data, next, next.y, pos.y + settings.headroom - 1)
+                }
+                0 => true,
+            };
+            clear.then_some(next)
+        })
+    })
+}
+
+fn heuristic(a: IVec3, b: IVec3) -> u32 {
+    ((a.x - b.x).unsigned_abs() + (a.z - b.z).unsigned_abs()) * STEP_COST
+}
+
+/// A* search between two walkable voxels, crossing chunk borders through world_data
+/// returns world positions at the feet of the agent (bottom center of each voxel),
+/// including start and goal. None if no path was found within settings.max_visited.
+pub fn find_path(
+    world_data: &HashMap<IVec3, Arc<ChunkData>>,
+    start: IVec3,
+    goal: IVec3,
+    settings: &PathSettings,
+) -> Option<Vec<Vec3>> {
+    if !is_walkable(world_data, start, settings) || !is_walkable(world_data, goal, settings) {
+        return None;
+    }
+
+    // pos -> (cost from start, previous pos)
+    let mut visited: HashMap<IVec3, (u32, IVec3)> = HashMap::new();
+    let mut open = BinaryHeap::new();
+    visited.insert(start, (0, start));
+    open.push(Reverse((heuristic(start, goal), 0u32, start.to_array())));
+
+    let mut expanded = 0;
+    while let Some(Reverse((_, cost, pos))) = open.pop() {
+        let pos = IVec3::from_array(pos);
+        if pos == goal {
+            return Some(reconstruct_path(&visited, start, goal));
+        }
+        // skip stale heap entries, a cheaper route was found after pushing
+        if visited.get(&pos).is_some_and(|(c, _)| *c < cost) {
+            continue;
+        }
+        expanded += 1;
+        if expanded > settings.max_visited {
+            return None;
+        }
+        for next in neighbours(world_data, pos, *settings) {
+            let next_cost = cost + STEP_COST + (next.y - pos.y).unsigned_abs() * VERTICAL_COST;
+            if visited.get(&next).is_some_and(|(c, _)| *c <= next_cost) {
+                continue;
+            }
+            visited.insert(next, (next_cost, pos));
+            open.push(Reverse((
+                next_cost + heuristic(next, goal),
+                next_cost,
+                next.to_array(),
+            )));
+        }
+    }
+    None
+}
+
+fn reconstruct_path(
+    visited: &HashMap<IVec3, (u32, IVec3)>,
+    start: IVec3,
+    goal: IVec3,
+) -> Vec<Vec3> {
+    let mut path = vec![goal];
+    let mut current = goal;
+    while current != start {
+        current = visited[&current].1;
+        path.push(current);
+    }
+    path.into_iter()
+        .rev()
+        .map(|p| p.as_vec3() + Vec3::new(0.5, 0.0, 0.5))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        constants::CHUNK_SIZE_I32,
+        test_utils::{chunk_from_fn, flat_world, set_block},
+        voxel::{BlockData, BlockType},
+    };
+
+    // fill the air chunks at y = 0 with dirt where the predicate is true,
+    // the predicate is called with world voxel positions
+    fn shape_ground(
+        world_data: &mut HashMap<IVec3, Arc<ChunkData>>,
+        solid: impl Fn(IVec3) -> bool,
+    ) {
+        for chunk_pos in world_data.keys().copied().collect::<Vec<_>>() {
+            if chunk_pos.y != 0 {
+                continue;
+            }
+            let chunk = chunk_from_fn(|pos| match solid(chunk_pos * CHUNK_SIZE_I32 + pos) {
+                true => BlockType::Dirt.into(),
+                false => BlockData::default(),
+            });
+            world_data.insert(chunk_pos, Arc::new(chunk));
+        }
+    }
+
+    // every step moves one voxel horizontally and lands on a walkable voxel
+    fn assert_valid_path(
+        world_data: &HashMap<IVec3, Arc<ChunkData>>,
+        path: &[Vec3],
+        settings: &PathSettings,
+    ) {
+        let voxels: Vec<IVec3> = path.iter().map(|p| p.floor().as_ivec3()).collect();
+        for pair in voxels.windows(2) {
+            let d = pair[1] - pair[0];
+            assert_eq!(d.x.abs() + d.z.abs(), 1, "{:?}", pair);
+            assert!(d.y <= settings.max_step_up && -d.y <= settings.max_drop);
+        }
+        for v in voxels {
+            assert!(is_walkable(world_data, v, settings), "{v}");
+        }
+    }
+
+    #[test]
+    fn straight_path_on_flat_ground() {
+        let world_data = flat_world(-1..=2);
+        let settings = PathSettings::default();
+        let path = find_path(
+            &world_data,
+            IVec3::new(2, 0, 2),
+            IVec3::new(12, 0, 7),
+            &settings,
+        )
+        .unwrap();
+        assert_eq!(path.len(), 16);
+        assert_eq!(path[0], Vec3::new(2.5, 0.0, 2.5));
+        assert_eq!(path[15], Vec3::new(12.5, 0.0, 7.5));
+        assert_valid_path(&world_data, &path, &settings);
+    }
+
+    #[test]
+    fn crosses_chunk_borders() {
+        let world_data = flat_world(-1..=2);
+        let settings = PathSettings::default();
+        let path = find_path(
+            &world_data,
+            IVec3::new(-5, 0, -3),
+            IVec3::new(40, 0, 3),
+            &settings,
+        )
+        .unwrap();
+        assert_valid_path(&world_data, &path, &settings);
+    }
+
+    #[test]
+    fn detours_around_walls() {
+        let mut world_data = flat_world(-1..=2);
+        shape_ground(&mut world_data, |p| p.x == 10 && p.z <= 20 && p.y < 3);
+        let settings = PathSettings::default();
+        let path = find_path(
+            &world_data,
+            IVec3::new(5, 0, 5),
+            IVec3::new(15, 0, 5),
+            &settings,
+        )
+        .unwrap();
+        assert!(path.len() > 11);
+        assert_valid_path(&world_data, &path, &settings);
+    }
+
+    #[test]
+    fn respects_step_up_limit() {
+        let mut world_data = flat_world(-1..=2);
+        // two voxel high plateau covering the rest of the chunk
+        shape_ground(&mut world_data, |p| p.x >= 10 && p.y < 2);
+        let start = IVec3::new(5, 0, 5);
+        let goal = IVec3::new(20, 2, 5);
+        let low = PathSettings::default();
+        assert!(find_path(&world_data, start, goal, &low).is_none());
+
+        let high = PathSettings {
+            max_step_up: 2,
+            ..default()
+        };
+        let path = find_path(&world_data, start, goal, &high).unwrap();
+        assert_valid_path(&world_data, &path, &high);
+    }
+
+    #[test]
+    fn respects_drop_limit() {
+        let mut world_data = flat_world(-1..=2);
+        shape_ground(&mut world_data, |p| p.x >= 10 && p.y < 2);
+        let start = IVec3::new(20, 2, 5);
+        let goal = IVec3::new(5, 0, 5);
+        let cautious = PathSettings {
+            max_drop: 1,
+            ..default()
+        };
+        assert!(find_path(&world_data, start, goal, &cautious).is_none());
+
+        let settings = PathSettings::default();
+        let path = find_path(&world_data, start, goal, &settings).unwrap();
+        assert_valid_path(&world_data, &path, &settings);
+    }
+
+    #[test]
+    fn needs_headroom() {
+        let mut world_data = flat_world(-1..=2);
+        // a one voxel high tunnel is the only way through the wall
+        shape_ground(&mut world_data, |p| p.x == 10 && (p.y > 0 || p.z != 5));
+        // block the way around the wall
+        for x in -1..=2 {
+            for z in [-1, 1] {
+                let solid = Arc::new(ChunkData::filled(BlockType::Dirt));
+                world_data.insert(IVec3::new(x, 0, z), solid);
+            }
+        }
+        let start = IVec3::new(5, 0, 5);
+        let goal = IVec3::new(15, 0, 5);
+        let crawler = PathSettings {
+            headroom: 1,
+            ..default()
+        };
+        let path = find_path(&world_data, start, goal, &crawler).unwrap();
+        assert_eq!(path.len(), 11);
+        assert_valid_path(&world_data, &path, &crawler);
+        let settings = PathSettings::default();
+        assert!(find_path(&world_data, start, goal, &settings).is_none());
+    }
+
+    #[test]
+    fn fluids_are_not_headroom() {
+        let mut world_data = flat_world(-1..=2);
+        let settings = PathSettings::default();
+        let (feet, head) = (IVec3::new(5, 0, 5), IVec3::new(6, 1, 5));
+        set_block(&mut world_data, feet, BlockType::Water.into());
+        set_block(&mut world_data, head, BlockType::Lava.into());
+        assert!(!is_walkable(&world_data, feet, &settings));
+        assert!(!is_walkable(&world_data, head - IVec3::Y, &settings));
+        assert!(is_walkable(&world_data, IVec3::new(7, 0, 5), &settings));
+    }
+
+    #[test]
+    fn paths_over_generated_terrain() {
+        let mut world_data = HashMap::new();
+        for z in -1..=1 {
+            for y in -2..=1 {
+                for x in -1..=1 {
+                    let pos = IVec3::new(x, y, z);
+                    world_data.insert(pos, Arc::new(ChunkData::generate(pos)));
+                }
+            }
+        }
+        let settings = PathSettings::default();
+        // first walkable voxel from the top of the loaded area
+        let surface = |x: i32, z: i32| {
+            (-60..60)
+                .rev()
+                .map(|y| IVec3::new(x, y, z))
+                .find(|p| is_walkable(&world_data, *p, &settings))
+                .unwrap()
+        };
+        // well inside the loaded chunks, whatever the chunk size
+        let r = CHUNK_SIZE_I32 * 5 / 8;
+        let start = surface(-r, -r);
+        let goal = surface(r, r);
+        let path = find_path(&world_data, start, goal, &settings).unwrap();
+        assert_valid_path(&world_data, &path, &settings);
+    }
+}
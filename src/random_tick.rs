@@ -0,0 +1,262 @@
+use std::sync::Arc;
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use rand::{Rng, RngCore};
+
+use crate::{
+    chunk::ChunkData,
+    constants::CHUNK_SIZE_I32,
+    scanner::Scanner,
+    utils::world_voxel_to_chunk,
+    voxel::{BlockData, BlockType},
+    voxel_engine::{get_world_block, start_modifications, ChunkModification, VoxelEngine},
+};
+
+pub struct RandomTickPlugin;
+
+impl Plugin for RandomTickPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RandomTickSettings>();
+        app.init_resource::<RandomTickHandlers>();
+        app.add_systems(Update, random_tick_chunks.before(start_modifications));
+    }
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct RandomTickSettings {
+    /// voxels picked in every ticked chunk, each frame
+    pub ticks_per_chunk: usize,
+    /// chunks further than this from a scanner (in chunks, per axis) are not ticked
+    pub chunk_radius: i32,
+}
+
+impl Default for RandomTickSettings {
+    fn default() -> Self {
+        Self {
+            ticks_per_chunk: 3,
+            chunk_radius: 4,
+        }
+    }
+}
+
+/// called with the ticked voxel and its block, returns the blocks to place
+pub type RandomTickHandler = Box<
+    dyn Fn(
+            &HashMap<IVec3, Arc<ChunkData>>,
+            IVec3,
+            BlockData,
+            &mut dyn RngCore,
+        ) -> Vec<(IVec3, BlockData)>
+        + Send
+        + Sync,
+>;
+
+/// per block type handlers, registered by gameplay code
+#[derive(Resource, Default)]
+pub struct RandomTickHandlers {
+    handlers: HashMap<BlockType, Vec<RandomTickHandler>>,
+}
+
+impl RandomTickHandlers {
+    pub fn register(
+        &mut self,
+        block_type: BlockType,
+        handler: impl Fn(
+                &HashMap<IVec3, Arc<ChunkData>>,
+                IVec3,
+                BlockData,
+                &mut dyn RngCore,
+            ) -> Vec<(IVec3, BlockData)>
+            + Send
+            + Sync
+            + 'static,
+    ) {
+        self.handlers
+            .entry(block_type)
+            .or_default()
+            .push(Box::new(handler));
+    }
+
+    pub fn has_handler(&self, block_type: BlockType) -> bool {
+        self.handlers.contains_key(&block_type)
+    }
+
+    /// run every handler registered for the block, collecting their changes
+    pub fn tick(
+        &self,
+        world_data: &HashMap<IVec3, Arc<ChunkData>>,
+        world_pos: IVec3,
+        block: BlockData,
+        rng: &mut dyn RngCore,
+    ) -> Vec<(IVec3, BlockData)> {
+        let Some(handlers) = self.handlers.get(&block.block_type) else {
+            return vec![];
+        };
+        handlers
+            .iter()
+            .flat_map(|handler| handler(world_data, world_pos, block, rng))
+            .collect()
+    }
+}
+
+/// grass dies when covered by a solid block,
+/// otherwise it spreads onto nearby dirt that has air above it
+pub fn spread_grass(
+    world_data: &HashMap<IVec3, Arc<ChunkData>>,
+    world_pos: IVec3,
+    _block: BlockData,
+    rng: &mut dyn RngCore,
+) -> Vec<(IVec3, BlockData)> {
+    let is_covered = |pos: IVec3| {
+        get_world_block(world_data, pos + IVec3::Y).is_some_and(|b| b.block_type.is_solid())
+    };
+    if is_covered(world_pos) {
+        return vec![(world_pos, BlockType::Dirt.into())];
+    }
+    let target = world_pos
+        + IVec3::new(
+            rng.gen_range(-1..=1),
+            rng.gen_range(-3..=1),
+            rng.gen_range(-1..=1),
+        );
+    let is_dirt =
+        get_world_block(world_data, target).is_some_and(|b| b.block_type == BlockType::Dirt);
+    match is_dirt && !is_covered(target) {
+        true => vec![(target, BlockType::Grass.into())],
+        false => vec![],
+    }
+}
+
+/// pick random voxels in the chunks around every scanner and run their handlers
+fn random_tick_chunks(
+    scanners: Query<&GlobalTransform, With<Scanner>>,
+    settings: Res<RandomTickSettings>,
+    handlers: Res<RandomTickHandlers>,
+    mut voxel_engine: ResMut<VoxelEngine>,
+) {
+    if handlers.handlers.is_empty() || settings.ticks_per_chunk == 0 {
+        return;
+    }
+    let r = settings.chunk_radius;
+    let mut chunks = HashSet::new();
+    for scanner_g in scanners.iter() {
+        let (scanner_chunk, _) = world_voxel_to_chunk(scanner_g.translation().floor().as_ivec3());
+        for z in -r..=r {
+            for y in -r..=r {
+                for x in -r..=r {
+                    chunks.insert(scanner_chunk + IVec3::new(x, y, z));
+                }
+            }
+        }
+    }
+
+    let mut rng = rand::thread_rng();
+    let mut changes = vec![];
+    for chunk_pos in chunks {
+        let Some(chunk) = voxel_engine.world_data.get(&chunk_pos) else {
+            continue;
+        };
+        // uniform chunks nobody listens to are skipped entirely
+        if chunk
+            .get_block_if_filled()
+            .is_some_and(|b| !handlers.has_handler(b.block_type))
+        {
+            continue;
+        }
+        for _ in 0..settings.ticks_per_chunk {
+            let local = IVec3::new(
+                rng.gen_range(0..CHUNK_SIZE_I32),
+                rng.gen_range(0..CHUNK_SIZE_I32),
+                rng.gen_range(0..CHUNK_SIZE_I32),
+            );
+            let world_pos = chunk_pos * CHUNK_SIZE_I32 + local;
+            let Some(block) = get_world_block(&voxel_engine.world_data, world_pos) else {
+                continue;
+            };
+            changes.extend(handlers.tick(&voxel_engine.world_data, world_pos, *block, &mut rng));
+        }
+    }
+
+    for (world_pos, block) in changes {
+        let (chunk_pos, local_pos) = world_voxel_to_chunk(world_pos);
+        voxel_engine
+            .chunk_modifications
+            .entry(chunk_pos)
+            .or_default()
+            .push(ChunkModification(local_pos, block));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{flat_world, set_block};
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn grass_spreads_onto_uncovered_dirt() {
+        let mut world_data = flat_world(-1..=1);
+        let grass = IVec3::new(5, -1, 5);
+        set_block(&mut world_data, grass, BlockType::Grass.into());
+        // covered dirt next to the grass never turns into grass
+        let covered = IVec3::new(6, -1, 5);
+        set_block(&mut world_data, covered + IVec3::Y, BlockType::Sand.into());
+
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut spread_to = HashSet::new();
+        for _ in 0..200 {
+            for (pos, block) in spread_grass(&world_data, grass, BlockType::Grass.into(), &mut rng)
+            {
+                assert_eq!(block.block_type, BlockType::Grass);
+                assert_eq!(pos.y, -1);
+                assert!((pos - grass).abs().max_element() <= 1);
+                spread_to.insert(pos);
+            }
+        }
+        assert!(!spread_to.contains(&covered));
+        // every other surface voxel around the grass was reached
+        assert_eq!(spread_to.len(), 7);
+    }
+
+    #[test]
+    fn covered_grass_turns_into_dirt() {
+        let mut world_data = flat_world(-1..=1);
+        let grass = IVec3::new(5, -1, 5);
+        set_block(&mut world_data, grass, BlockType::Grass.into());
+        set_block(&mut world_data, grass + IVec3::Y, BlockType::Sand.into());
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let changes = spread_grass(&world_data, grass, BlockType::Grass.into(), &mut rng);
+        assert_eq!(changes, [(grass, BlockType::Dirt.into())]);
+    }
+
+    #[test]
+    fn handlers_run_per_block_type() {
+        let world_data = flat_world(-1..=1);
+        let mut handlers = RandomTickHandlers::default();
+        handlers.register(BlockType::Dirt, |_, pos, _, _| {
+            vec![(pos, BlockType::Sand.into())]
+        });
+        handlers.register(BlockType::Dirt, |_, pos, _, _| {
+            vec![(pos + IVec3::Y, BlockType::Gravel.into())]
+        });
+        assert!(handlers.has_handler(BlockType::Dirt));
+        assert!(!handlers.has_handler(BlockType::Grass));
+
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let pos = IVec3::new(0, -1, 0);
+        let changes = handlers.tick(&world_data, pos, BlockType::Dirt.into(), &mut rng);
+        assert_eq!(
+            changes,
+            [
+                (pos, BlockType::Sand.into()),
+                (IVec3::ZERO, BlockType::Gravel.into())
+            ]
+        );
+        let changes = handlers.tick(&world_data, IVec3::ZERO, BlockType::Air.into(), &mut rng);
+        assert!(changes.is_empty());
+    }
+}
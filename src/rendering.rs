@@ -1,8 +1,11 @@
+use std::sync::OnceLock;
+
 use bevy::{
     pbr::{MaterialPipeline, MaterialPipelineKey},
     prelude::*,
     render::{
-        mesh::{MeshVertexAttribute, MeshVertexBufferLayout},
+        mesh::{Indices, MeshVertexAttribute, MeshVertexBufferLayout, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
         render_resource::{
             AsBindGroup, PolygonMode, RenderPipelineDescriptor, ShaderRef,
             SpecializedMeshPipelineError, VertexFormat,
@@ -10,6 +13,11 @@ use bevy::{
     },
 };
 
+use crate::{
+    chunk_mesh::SmoothMesh,
+    voxel::{BlockType, MESHABLE_BLOCK_TYPES},
+};
+
 #[derive(Resource)]
 pub enum ChunkMaterialWireframeMode {
     On,
@@ -22,6 +30,7 @@ impl Plugin for RenderingPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(MaterialPlugin::<ChunkMaterial>::default());
         app.add_plugins(MaterialPlugin::<ChunkMaterialWireframe>::default());
+        app.add_plugins(MaterialPlugin::<ChunkQuadMaterial>::default());
         app.insert_resource(ChunkMaterialWireframeMode::Off);
         app.add_systems(Update, apply_chunk_material);
     }
@@ -68,6 +77,13 @@ fn apply_chunk_material(
 pub struct GlobalChunkMaterial(pub Handle<ChunkMaterial>);
 #[derive(Resource, Reflect)]
 pub struct GlobalChunkWireframeMaterial(pub Handle<ChunkMaterialWireframe>);
+/// parameters for chunks using packed quads, every chunk gets its own copy holding its quads
+#[derive(Resource, Reflect)]
+pub struct GlobalChunkQuadMaterial(pub ChunkQuadMaterial);
+/// material of smooth chunks (see smooth_mesher), colored by their vertex colors.
+/// these chunks are not affected by the wireframe toggle
+#[derive(Resource, Reflect)]
+pub struct GlobalSmoothChunkMaterial(pub Handle<StandardMaterial>);
 
 // A "high" random id should be used for custom attributes to ensure consistent sorting and avoid collisions with other attributes.
 // See the MeshVertexAttribute docs for more info.
@@ -159,3 +175,200 @@ impl Material for ChunkMaterialWireframe {
         "shaders/chunk_prepass.wgsl".into()
     }
 }
+
+// chunk material reading packed quads (see packed_quad) from a storage buffer,
+// the vertex shader expands them from the vertex index instead of reading vertex attributes
+#[derive(Asset, Reflect, AsBindGroup, Debug, Clone)]
+pub struct ChunkQuadMaterial {
+    #[uniform(0)]
+    pub reflectance: f32,
+    #[uniform(0)]
+    pub perceptual_roughness: f32,
+    #[uniform(0)]
+    pub metallic: f32,
+    /// packed u64 quads split into (low, high) bits, wgsl has no u64
+    #[storage(1, read_only)]
+    pub quads: Vec<UVec2>,
+}
+
+impl ChunkQuadMaterial {
+    /// copy of this material drawing the given quads
+    pub fn with_quads(&self, quads: &[u64]) -> Self {
+        Self {
+            quads: quads
+                .iter()
+                .map(|q| UVec2::new(*q as u32, (*q >> 32) as u32))
+                .collect(),
+            ..self.clone()
+        }
+    }
+}
+
+impl Material for ChunkQuadMaterial {
+    fn vertex_shader() -> ShaderRef {
+        "shaders/chunk.wgsl".into()
+    }
+    fn fragment_shader() -> ShaderRef {
+        "shaders/chunk.wgsl".into()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        AlphaMode::Opaque
+    }
+
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayout,
+        _key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        descriptor.vertex.shader_defs.push("PACKED_QUADS".into());
+        descriptor.vertex.buffers = vec![];
+        Ok(())
+    }
+
+    fn prepass_vertex_shader() -> ShaderRef {
+        "shaders/chunk_prepass.wgsl".into()
+    }
+
+    fn prepass_fragment_shader() -> ShaderRef {
+        "shaders/chunk_prepass.wgsl".into()
+    }
+}
+
+/// mesh of u32 voxel vertices, see make_vertex_u32, drawn with ChunkMaterial
+pub fn voxel_chunk_mesh(vertices: Vec<u32>, indices: Vec<u32>) -> Mesh {
+    let mut mesh = Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    mesh.insert_attribute(ATTRIBUTE_VOXEL, vertices);
+    mesh.insert_indices(Indices::U32(indices));
+    mesh
+}
+
+/// mesh drawing quad_count packed quads, it only holds the indices 0..6 * quad_count
+/// (u16 when they fit) so the vertex shader knows which quad and corner to expand
+pub fn packed_quad_mesh(quad_count: usize) -> Mesh {
+    let vertex_count = quad_count * 6;
+    let indices = match vertex_count <= u16::MAX as usize + 1 {
+        true => Indices::U16((0..vertex_count as u32).map(|i| i as u16).collect()),
+        false => Indices::U32((0..vertex_count as u32).collect()),
+    };
+    let mut mesh = Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    mesh.insert_indices(indices);
+    mesh
+}
+
+// the block palette lives in the block_color array of the chunk shader
+const CHUNK_SHADER: &str = include_str!("../assets/shaders/chunk.wgsl");
+
+/// linear colors of the block_color array in shaders/chunk.wgsl, indexed by BlockType
+pub fn block_palette() -> &'static [[f32; 3]] {
+    static PALETTE: OnceLock<Vec<[f32; 3]>> = OnceLock::new();
+    PALETTE.get_or_init(|| parse_block_palette(CHUNK_SHADER))
+}
+
+// the vec3 entries of the block_color array, one per line
+fn parse_block_palette(shader: &str) -> Vec<[f32; 3]> {
+    let start = shader
+        .find("var<private> block_color")
+        .expect("chunk shader declares block_color");
+    shader[start..]
+        .lines()
+        .skip(1)
+        .take_while(|line| !line.trim_start().starts_with(");"))
+        .map(|line| {
+            let (_, args) = line.split_once("vec3<f32>(").expect("block_color entry");
+            let (args, _) = args.split_once(')').expect("block_color entry");
+            let mut rgb = args.split(',').map(|v| v.trim().parse::<f32>().unwrap());
+            [(); 3].map(|_| rgb.next().expect("3 color channels"))
+        })
+        .collect()
+}
+
+/// linear color of a block, as drawn by shaders/chunk.wgsl
+pub fn block_color(block_type: BlockType) -> [f32; 3] {
+    block_palette()[block_type as usize]
+}
+
+/// mesh of a smooth chunk, vertex colors blend the block colors by material weight
+pub fn smooth_chunk_mesh(smooth: SmoothMesh) -> Mesh {
+    let colors: Vec<[f32; 4]> = smooth
+        .weights
+        .iter()
+        .map(|weights| {
+            let mut color = [0.0, 0.0, 0.0, 1.0];
+            for (weight, block_type) in weights.iter().zip(MESHABLE_BLOCK_TYPES) {
+                let block_color = block_color(*block_type);
+                (0..3).for_each(|i| color[i] += block_color[i] * weight);
+            }
+            color
+        })
+        .collect();
+    let mut mesh = Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, smooth.positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, smooth.normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    mesh.insert_indices(Indices::U32(smooth.indices));
+    mesh
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // name of the block in the comments of the shader's block_color array,
+    // a new BlockType doesn't compile until it gets a color
+    fn shader_name(block_type: BlockType) -> &'static str {
+        match block_type {
+            BlockType::Air => "air",
+            BlockType::Grass => "grass",
+            BlockType::Dirt => "dirt",
+            BlockType::Water => "water",
+            BlockType::Lava => "lava",
+            BlockType::Sand => "sand",
+            BlockType::Gravel => "gravel",
+        }
+    }
+
+    #[test]
+    fn palette_has_a_color_for_every_block_type() {
+        let block_types = [
+            BlockType::Air,
+            BlockType::Grass,
+            BlockType::Dirt,
+            BlockType::Water,
+            BlockType::Lava,
+            BlockType::Sand,
+            BlockType::Gravel,
+        ];
+        let start = CHUNK_SHADER.find("var<private> block_color").unwrap();
+        let declaration = CHUNK_SHADER[start..].lines().next().unwrap();
+        let len = format!("array<vec3<f32>,{}>", block_types.len());
+        assert!(declaration.contains(&len), "{declaration}");
+
+        let names: Vec<&str> = CHUNK_SHADER[start..]
+            .lines()
+            .skip(1)
+            .take(block_types.len())
+            .map(|line| line.split("//").nth(1).unwrap().trim())
+            .collect();
+        assert_eq!(block_palette().len(), block_types.len());
+        for (i, block_type) in block_types.into_iter().enumerate() {
+            assert_eq!(block_type as usize, i);
+            assert_eq!(names[i], shader_name(block_type));
+        }
+        assert_eq!(block_color(BlockType::Dirt), [0.3, 0.4, 0.0]);
+        assert!(block_palette()
+            .iter()
+            .flatten()
+            .all(|c| (0.0..=1.0).contains(c)));
+    }
+}
@@ -1,37 +1,46 @@
 use std::collections::VecDeque;
 
-use bevy::{prelude::*, utils::HashSet};
+use bevy::{
+    math::{Affine3A, Vec3A},
+    prelude::*,
+    render::primitives::{Aabb, Frustum},
+    utils::{HashMap, HashSet},
+};
 
 use crate::{
-    constants::ADJACENT_CHUNK_DIRECTIONS, utils::index_to_ivec3_bounds, voxel_engine::VoxelEngine,
+    constants::{ADJACENT_CHUNK_DIRECTIONS, CHUNK_SIZE},
+    load_area::{ChunkArea, LoadShape},
+    utils::world_to_chunk,
+    voxel_engine::{ChunkWorkBudget, VoxelEngine},
 };
 
-pub const MAX_DATA_TASKS: usize = 9;
-pub const MAX_MESH_TASKS: usize = 3;
-
 pub const MAX_SCANS: usize = 26000;
 
+/// load priority multiplier for chunks inside a scanner's camera frustum
+pub const FRUSTUM_PRIORITY: f32 = 0.25;
+/// load priority multiplier for chunks straight ahead in the direction of travel,
+/// interpolated towards 1.0 for chunks off to the side or behind
+pub const TRAVEL_PRIORITY: f32 = 0.5;
+
 pub struct ScannerPlugin;
 
 impl Plugin for ScannerPlugin {
     fn build(&self, app: &mut App) {
+        app.init_resource::<ChunkInterest>();
+        app.init_resource::<ChunkWorkBudget>();
         app.add_systems(
             PreUpdate,
             (
-                detect_move,
-                scan_data,
-                scan_data_unload,
-                scan_mesh_unload,
-                scan_mesh,
+                track_travel_direction,
+                (release_removed_scanners, detect_move).chain(),
+                (scan_data, scan_data_unload, scan_mesh_unload, scan_mesh).after(detect_move),
             ),
         );
     }
 }
 
 ///! scanner is responsible for identifying what chunks needs to be loaded (mesh/data)
-///! the current implementation is exellent for low render distances, 1-15
-///! but anything above that might induce some frame lag, due to how the load/unload data is calculated.  
-///! scanner::new() can also be very slow on high render distances, giving an initial slow execution time.
+///! the load/unload areas are computed row by row on move, so large view distances (32+) stay cheap.
 #[derive(Component)]
 pub struct Scanner {
     pub prev_chunk_pos: IVec3,
@@ -50,81 +59,280 @@ pub struct Scanner {
     pub unresolved_data_unload: VecDeque<IVec3>,
     pub unresolved_mesh_unload: VecDeque<IVec3>,
 
-    // on detecting a scanner move, these offsets are used to
-    // identify the location of what chunks need to be checked
-    pub data_sampling_offsets: Vec<IVec3>,
-    pub mesh_sampling_offsets: Vec<IVec3>,
+    /// chunks within this distance get meshed, data is loaded one chunk further.
+    /// can be changed at runtime, detect_move loads/unloads the difference
+    pub distance: i32,
+    /// shape of the loaded region, shared by the data and mesh areas
+    pub shape: LoadShape,
+
+    /// last world position, used to track the direction of travel
+    pub prev_translation: Vec3,
+    /// smoothed direction the scanner is moving in, zero when standing still
+    pub travel_direction: Vec3,
+}
+
+/// where a scanner is, what it's looking at and where it's going, used to order chunk loading
+#[derive(Clone, Copy)]
+pub struct ScannerView {
+    pub chunk_pos: IVec3,
+    pub frustum: Option<Frustum>,
+    pub travel_direction: Vec3,
+}
+
+impl ScannerView {
+    /// squared chunk distance, scaled down for chunks inside the frustum
+    /// and chunks in the direction of travel. lower loads first
+    pub fn load_priority(&self, chunk_pos: IVec3) -> f32 {
+        let offset = chunk_pos - self.chunk_pos;
+        let mut priority = offset.length_squared() as f32;
+        let chunk_aabb = Aabb {
+            center: Vec3A::splat(CHUNK_SIZE as f32 * 0.5),
+            half_extents: Vec3A::splat(CHUNK_SIZE as f32 * 0.5),
+        };
+        let chunk_transform = Affine3A::from_translation(chunk_pos.as_vec3() * CHUNK_SIZE as f32);
+        if self
+            .frustum
+            .is_some_and(|f| f.intersects_obb(&chunk_aabb, &chunk_transform, true, false))
+        {
+            priority *= FRUSTUM_PRIORITY;
+        }
+        let alignment = offset
+            .as_vec3()
+            .normalize_or_zero()
+            .dot(self.travel_direction)
+            .max(0.0);
+        priority * (1.0 - alignment * (1.0 - TRAVEL_PRIORITY))
+    }
+}
+
+/// best priority any scanner gives the chunk, as a sortable key
+pub fn load_priority(chunk_pos: IVec3, views: &[ScannerView]) -> i64 {
+    views
+        .iter()
+        .map(|view| (view.load_priority(chunk_pos) * 64.0) as i64)
+        .min()
+        .unwrap_or(0)
+}
+
+/// the areas a single scanner currently keeps loaded
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScannerClaims {
+    pub data: ChunkArea,
+    pub mesh: ChunkArea,
+}
+
+/// reference counts how many scanners want each chunk loaded,
+/// a chunk is only unloaded once no scanner is interested in it anymore
+#[derive(Resource, Default)]
+pub struct ChunkInterest {
+    data: HashMap<IVec3, u32>,
+    mesh: HashMap<IVec3, u32>,
+    claims: HashMap<Entity, ScannerClaims>,
+}
+
+// true if this was the first claim on pos
+fn claim(counts: &mut HashMap<IVec3, u32>, pos: IVec3) -> bool {
+    let count = counts.entry(pos).or_insert(0);
+    *count += 1;
+    *count == 1
+}
+
+// true if this was the last claim on pos
+fn release(counts: &mut HashMap<IVec3, u32>, pos: IVec3) -> bool {
+    let Some(count) = counts.get_mut(&pos) else {
+        return false;
+    };
+    *count -= 1;
+    if *count > 0 {
+        return false;
+    }
+    counts.remove(&pos);
+    true
+}
+
+impl ChunkInterest {
+    pub fn wants_data(&self, chunk_pos: IVec3) -> bool {
+        self.data.contains_key(&chunk_pos)
+    }
+
+    pub fn wants_mesh(&self, chunk_pos: IVec3) -> bool {
+        self.mesh.contains_key(&chunk_pos)
+    }
+
+    /// every chunk at least one scanner wants meshed
+    pub fn mesh_chunks(&self) -> impl Iterator<Item = IVec3> + '_ {
+        self.mesh.keys().copied()
+    }
+
+    pub fn claims(&self, scanner: Entity) -> Option<&ScannerClaims> {
+        self.claims.get(&scanner)
+    }
+
+    /// replace the area claimed by scanner, returns the chunks that gained their
+    /// first claim (need loading) and the chunks that lost their last claim (need unloading)
+    pub fn update_claims(&mut self, scanner: Entity, claims: ScannerClaims) -> ClaimChanges {
+        let previous = self.claims.insert(scanner, claims.clone());
+        let mut changes = ClaimChanges::default();
+        for pos in claims.data.difference(previous.as_ref().map(|p| &p.data)) {
+            if claim(&mut self.data, pos) {
+                changes.data_load.push(pos);
+            }
+        }
+        for pos in claims.mesh.difference(previous.as_ref().map(|p| &p.mesh)) {
+            if claim(&mut self.mesh, pos) {
+                changes.mesh_load.push(pos);
+            }
+        }
+        let Some(previous) = previous else {
+            return changes;
+        };
+        for pos in previous.data.difference(Some(&claims.data)) {
+            if release(&mut self.data, pos) {
+                changes.data_unload.push(pos);
+            }
+        }
+        for pos in previous.mesh.difference(Some(&claims.mesh)) {
+            if release(&mut self.mesh, pos) {
+                changes.mesh_unload.push(pos);
+            }
+        }
+        changes
+    }
+
+    /// drop everything claimed by scanner
+    pub fn remove_scanner(&mut self, scanner: Entity) -> ClaimChanges {
+        let mut changes = ClaimChanges::default();
+        let Some(previous) = self.claims.remove(&scanner) else {
+            return changes;
+        };
+        for pos in previous.data.iter() {
+            if release(&mut self.data, pos) {
+                changes.data_unload.push(pos);
+            }
+        }
+        for pos in previous.mesh.iter() {
+            if release(&mut self.mesh, pos) {
+                changes.mesh_unload.push(pos);
+            }
+        }
+        changes
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct ClaimChanges {
+    pub data_load: Vec<IVec3>,
+    pub data_unload: Vec<IVec3>,
+    pub mesh_load: Vec<IVec3>,
+    pub mesh_unload: Vec<IVec3>,
 }
 
 impl Scanner {
-    ///! construct scanner, chunk offsets are based on distance
-    ///! warning: slow execution time on distances above 15-20,
+    ///! construct scanner, chunks are meshed up to distance away
     pub fn new(distance: i32) -> Self {
-        let data_distance = distance + 1;
-        let mesh_distance = distance;
-        let data_sampling_offsets = make_offset_vec(data_distance);
-        let mesh_sampling_offsets = make_offset_vec(mesh_distance);
         Self {
             checks_per_frame: 32 * 32 * 32,
             data_offset: 0,
-            data_sampling_offsets,
-            mesh_sampling_offsets,
             mesh_offset: 0,
             unresolved_data_load: Vec::default(),
-            prev_chunk_pos: IVec3::new(777,777,777),
+            prev_chunk_pos: IVec3::new(777, 777, 777),
             unresolved_mesh_load: Vec::default(),
             unresolved_data_unload: VecDeque::default(),
             unresolved_mesh_unload: VecDeque::default(),
+            distance,
+            shape: LoadShape::Cube,
+            prev_translation: Vec3::ZERO,
+            travel_direction: Vec3::ZERO,
+        }
+    }
+
+    pub fn with_shape(mut self, shape: LoadShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    /// the areas this scanner wants loaded when standing in chunk_pos
+    pub fn claims_at(&self, chunk_pos: IVec3) -> ScannerClaims {
+        let mesh = ChunkArea::with_shape(chunk_pos, self.distance.max(0), self.shape.clone());
+        ScannerClaims {
+            data: mesh.clone().with_margin(1),
+            mesh,
         }
     }
 }
 
-///! on scanner chunk change, enqueue chunks to load/unload
+/// smooth out the per frame movement of every scanner into its direction of travel
+fn track_travel_direction(mut scanners: Query<(&mut Scanner, &GlobalTransform)>) {
+    for (mut scanner, g_transform) in scanners.iter_mut() {
+        let translation = g_transform.translation();
+        let delta = translation - scanner.prev_translation;
+        scanner.prev_translation = translation;
+        // teleports don't count as travel
+        let direction = match delta.length() < CHUNK_SIZE as f32 {
+            true => delta.normalize_or_zero(),
+            false => Vec3::ZERO,
+        };
+        scanner.travel_direction = scanner.travel_direction.lerp(direction, 0.1);
+    }
+}
+
+/// release the chunks claimed by despawned scanners
+fn release_removed_scanners(
+    mut removed: RemovedComponents<Scanner>,
+    mut chunk_interest: ResMut<ChunkInterest>,
+    mut voxel_engine: ResMut<VoxelEngine>,
+) {
+    for entity in removed.read() {
+        let changes = chunk_interest.remove_scanner(entity);
+        let VoxelEngine {
+            load_data_queue,
+            load_mesh_queue,
+            unload_data_queue,
+            unload_mesh_queue,
+            ..
+        } = voxel_engine.as_mut();
+        load_data_queue.retain(|p| !changes.data_unload.contains(p));
+        load_mesh_queue.retain(|p| !changes.mesh_unload.contains(p));
+        unload_data_queue.extend(changes.data_unload);
+        unload_mesh_queue.extend(changes.mesh_unload);
+    }
+}
+
+///! on scanner chunk or distance change, enqueue chunks to load/unload
+///! only chunks no other scanner is interested in are loaded or unloaded
 fn detect_move(
-    mut scanners: Query<(&mut Scanner, &GlobalTransform)>,
+    mut scanners: Query<(Entity, &mut Scanner, &GlobalTransform)>,
     mut voxel_engine: ResMut<VoxelEngine>,
+    mut chunk_interest: ResMut<ChunkInterest>,
 ) {
-    for (mut scanner, g_transform) in scanners.iter_mut() {
-        let chunk_pos = ((g_transform.translation() - Vec3::splat(16.0)) * (1.0 / 32.0)).as_ivec3();
-        let previous_chunk_pos = scanner.prev_chunk_pos;
-        let chunk_pos_changed = chunk_pos != scanner.prev_chunk_pos;
+    for (entity, mut scanner, g_transform) in scanners.iter_mut() {
+        let chunk_pos = world_to_chunk(g_transform.translation());
         scanner.prev_chunk_pos = chunk_pos;
-        if !chunk_pos_changed {
-            return;
+        let claims = scanner.claims_at(chunk_pos);
+        if chunk_interest.claims(entity) == Some(&claims) {
+            continue;
         }
-        let load_data_area = scanner
-            .data_sampling_offsets
-            .iter()
-            .map(|offset| chunk_pos + *offset)
-            .collect::<HashSet<IVec3>>();
-
-        let unload_data_area = scanner
-            .data_sampling_offsets
-            .iter()
-            .map(|offset| previous_chunk_pos + *offset)
-            .collect::<HashSet<IVec3>>();
-
-        let load_mesh_area = scanner
-            .mesh_sampling_offsets
-            .iter()
-            .map(|offset| chunk_pos + *offset)
-            .collect::<HashSet<IVec3>>();
-
-        let unload_mesh_area = scanner
-            .mesh_sampling_offsets
-            .iter()
-            .map(|offset| previous_chunk_pos + *offset)
-            .collect::<HashSet<IVec3>>();
+        let changes = chunk_interest.update_claims(entity, claims);
 
-        let data_load = load_data_area.difference(&unload_data_area);
-        let data_unload = unload_data_area.difference(&load_data_area);
-        let mesh_load = load_mesh_area.difference(&unload_mesh_area);
-        let mesh_unload = unload_mesh_area.difference(&load_mesh_area);
+        // chunks released by a despawned scanner this frame might be wanted again
+        let VoxelEngine {
+            unload_data_queue,
+            unload_mesh_queue,
+            ..
+        } = voxel_engine.as_mut();
+        if !unload_data_queue.is_empty() {
+            let data_loads = changes.data_load.iter().collect::<HashSet<_>>();
+            unload_data_queue.retain(|p| !data_loads.contains(p));
+        }
+        if !unload_mesh_queue.is_empty() {
+            let mesh_loads = changes.mesh_load.iter().collect::<HashSet<_>>();
+            unload_mesh_queue.retain(|p| !mesh_loads.contains(p));
+        }
 
-        scanner.unresolved_data_load.extend(data_load);
-        scanner.unresolved_data_unload.extend(data_unload);
-        scanner.unresolved_mesh_unload.extend(mesh_unload);
-        scanner.unresolved_mesh_load.extend(mesh_load);
+        scanner.unresolved_data_load.extend(changes.data_load);
+        scanner.unresolved_data_unload.extend(changes.data_unload);
+        scanner.unresolved_mesh_unload.extend(changes.mesh_unload);
+        scanner.unresolved_mesh_load.extend(changes.mesh_load);
 
         // deconstruct scanner mutable references because rust :P
         let Scanner {
@@ -135,37 +343,26 @@ fn detect_move(
             ..
         } = scanner.as_mut();
 
-        for p in unresolved_mesh_unload.iter() {
-            if let Some((i, _)) = voxel_engine
-                .load_mesh_queue
-                .iter()
-                .enumerate()
-                .find(|(_i, k)| *k == p)
-            {
-                voxel_engine.load_mesh_queue.remove(i);
-            }
-        }
-        for p in unresolved_data_unload.iter() {
-            if let Some((i, _)) = voxel_engine
-                .load_data_queue
-                .iter()
-                .enumerate()
-                .find(|(_i, k)| *k == p)
-            {
-                voxel_engine.load_data_queue.remove(i);
-            }
-        }
+        // hashed, both sides can be tens of thousands of chunks on large distances
+        let mesh_unloads = unresolved_mesh_unload
+            .iter()
+            .copied()
+            .collect::<HashSet<_>>();
+        let data_unloads = unresolved_data_unload
+            .iter()
+            .copied()
+            .collect::<HashSet<_>>();
+        voxel_engine
+            .load_mesh_queue
+            .retain(|p| !mesh_unloads.contains(p));
+        voxel_engine
+            .load_data_queue
+            .retain(|p| !data_unloads.contains(p));
 
         // remove the unloads from load
-        unresolved_mesh_load.retain(|p| {
-            let want_unload = unresolved_mesh_unload.contains(p);
-            !want_unload
-        });
+        unresolved_mesh_load.retain(|p| !mesh_unloads.contains(p));
         // remove the unloads from load
-        unresolved_data_load.retain(|p| {
-            let want_unload = unresolved_data_unload.contains(p);
-            !want_unload
-        });
+        unresolved_data_load.retain(|p| !data_unloads.contains(p));
 
         scanner.unresolved_mesh_load.sort_by(|a, b| {
             a.distance_squared(chunk_pos)
@@ -178,29 +375,13 @@ fn detect_move(
     }
 }
 
-///! constructs spherical positions with the provided chunk radius
-fn make_offset_vec(half: i32) -> Vec<IVec3> {
-    let k = (half * 2) + 1;
-    let mut sampling_offsets = vec![];
-    for i in 0..k * k * k {
-        let mut pos = index_to_ivec3_bounds(i, k);
-        pos -= IVec3::splat((k as f32 * 0.5) as i32);
-
-        sampling_offsets.push(pos);
-    }
-    sampling_offsets.sort_by(|a, b| {
-        a.distance_squared(IVec3::ZERO)
-            .cmp(&b.distance_squared(IVec3::ZERO))
-    });
-    sampling_offsets
-}
-
 pub fn scan_data(
     mut scanners: Query<(&mut Scanner, &GlobalTransform)>,
     mut voxel_engine: ResMut<VoxelEngine>,
+    budget: Res<ChunkWorkBudget>,
 ) {
     for (mut scanner, _g_transform) in scanners.iter_mut() {
-        if voxel_engine.data_tasks.len() >= MAX_DATA_TASKS {
+        if voxel_engine.data_tasks.len() >= budget.max_data_tasks {
             return;
         }
         let l = scanner.unresolved_data_load.len();
@@ -231,12 +412,14 @@ pub fn scan_data(
 pub fn scan_data_unload(
     mut scanners: Query<(&mut Scanner, &GlobalTransform)>,
     mut voxel_engine: ResMut<VoxelEngine>,
+    chunk_interest: Res<ChunkInterest>,
 ) {
     // find all loaded and check if in range
     for (mut scanner, _g_transform) in scanners.iter_mut() {
         for chunk_pos in scanner.unresolved_data_unload.drain(..) {
-            // want to load chunk
-            let is_busy = !voxel_engine.world_data.contains_key(&chunk_pos);
+            // another scanner claimed it again in the meantime
+            let is_busy = !voxel_engine.world_data.contains_key(&chunk_pos)
+                || chunk_interest.wants_data(chunk_pos);
             if !is_busy {
                 voxel_engine.unload_data_queue.push(chunk_pos);
             }
@@ -244,10 +427,17 @@ pub fn scan_data_unload(
     }
 }
 
-pub fn scan_mesh_unload(mut scanners: Query<&mut Scanner>, mut voxel_engine: ResMut<VoxelEngine>) {
+pub fn scan_mesh_unload(
+    mut scanners: Query<&mut Scanner>,
+    mut voxel_engine: ResMut<VoxelEngine>,
+    chunk_interest: Res<ChunkInterest>,
+) {
     // find all loaded and check if in range
     for mut scanner in scanners.iter_mut() {
         for chunk_pos in scanner.unresolved_mesh_unload.drain(..) {
+            if chunk_interest.wants_mesh(chunk_pos) {
+                continue;
+            }
             voxel_engine.unload_mesh_queue.push(chunk_pos);
         }
     }
@@ -255,9 +445,6 @@ pub fn scan_mesh_unload(mut scanners: Query<&mut Scanner>, mut voxel_engine: Res
 
 pub fn scan_mesh(mut scanners: Query<&mut Scanner>, mut voxel_engine: ResMut<VoxelEngine>) {
     for mut scanner in scanners.iter_mut() {
-        // if voxel_engine.data_tasks.len() >= MAX_MESH_TASKS {
-        //     return;
-        // }
         let mut retries = Vec::new();
         let l = scanner.unresolved_mesh_load.len();
         for chunk_pos in scanner.unresolved_mesh_load.drain(0..MAX_SCANS.min(l)) {
@@ -288,3 +475,160 @@ pub fn scan_mesh(mut scanners: Query<&mut Scanner>, mut voxel_engine: ResMut<Vox
         scanner.unresolved_mesh_load.append(&mut retries);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{chunk::ChunkData, voxel::BlockType};
+
+    fn area(center: IVec3, half: i32) -> HashSet<IVec3> {
+        ChunkArea::new(center, half).iter().collect()
+    }
+
+    #[test]
+    fn changing_distance_only_touches_the_border() {
+        let mut interest = ChunkInterest::default();
+        let entity = Entity::from_raw(0);
+        let mut scanner = Scanner::new(32);
+        let changes = interest.update_claims(entity, scanner.claims_at(IVec3::ZERO));
+        assert_eq!(changes.data_load.len(), 67usize.pow(3));
+        assert_eq!(changes.mesh_load.len(), 65usize.pow(3));
+
+        let changes = interest.update_claims(entity, scanner.claims_at(IVec3::X));
+        assert_eq!(changes.data_load.len(), 67 * 67);
+        assert_eq!(changes.data_unload.len(), 67 * 67);
+        assert!(changes.data_unload.iter().all(|p| p.x == -33));
+
+        scanner.distance = 30;
+        let changes = interest.update_claims(entity, scanner.claims_at(IVec3::X));
+        assert!(changes.mesh_load.is_empty());
+        assert_eq!(changes.mesh_unload.len(), 65usize.pow(3) - 61usize.pow(3));
+        assert!(changes
+            .mesh_unload
+            .iter()
+            .all(|p| (*p - IVec3::X).abs().max_element() > 30));
+    }
+
+    #[test]
+    fn claims_are_reference_counted() {
+        let mut interest = ChunkInterest::default();
+        let (a, b) = (Entity::from_raw(0), Entity::from_raw(1));
+        let claims = |center| ScannerClaims {
+            data: ChunkArea::new(center, 1),
+            mesh: ChunkArea::new(center, 0),
+        };
+        let changes = interest.update_claims(a, claims(IVec3::ZERO));
+        assert_eq!(changes.data_load.len(), 27);
+        assert_eq!(changes.mesh_load, [IVec3::ZERO]);
+
+        // overlapping area only loads what wasn't wanted yet
+        let changes = interest.update_claims(b, claims(IVec3::X));
+        assert_eq!(changes.data_load.len(), 9);
+        assert_eq!(changes.mesh_load, [IVec3::X]);
+
+        // a moves away, chunks b still wants are kept
+        let changes = interest.update_claims(a, claims(IVec3::X * 10));
+        assert_eq!(changes.data_unload.len(), 9);
+        assert!(changes.data_unload.iter().all(|p| p.x == -1));
+        assert!(interest.wants_data(IVec3::ZERO));
+
+        let changes = interest.remove_scanner(b);
+        assert_eq!(changes.data_unload.len(), 27);
+        assert!(changes.data_load.is_empty());
+        assert!(!interest.wants_data(IVec3::ZERO));
+        assert!(interest.claims(b).is_none());
+        assert_eq!(interest.mesh_chunks().collect::<Vec<_>>(), [IVec3::X * 10]);
+    }
+
+    #[test]
+    fn priority_favours_view_and_travel() {
+        // camera in the middle of chunk 0, looking down -z
+        let eye = Vec3::splat(16.0);
+        let view = Mat4::from_translation(eye).inverse();
+        let projection = Mat4::perspective_infinite_reverse_rh(1.0, 1.0, 0.1);
+        let frustum = Frustum::from_view_projection(&(projection * view));
+        let ahead = IVec3::new(0, 0, -6);
+        let behind = IVec3::new(0, 0, 6);
+        let looking = [ScannerView {
+            chunk_pos: IVec3::ZERO,
+            frustum: Some(frustum),
+            travel_direction: Vec3::ZERO,
+        }];
+        assert!(load_priority(ahead, &looking) < load_priority(behind, &looking));
+        // the chunks right around the scanner still come before far away chunks in view
+        assert!(load_priority(IVec3::new(0, 0, 2), &looking) < load_priority(ahead, &looking));
+
+        let moving = [ScannerView {
+            chunk_pos: IVec3::ZERO,
+            frustum: None,
+            travel_direction: Vec3::X,
+        }];
+        let right = IVec3::new(6, 0, 0);
+        assert!(load_priority(right, &moving) < load_priority(-right, &moving));
+        assert_eq!(
+            load_priority(ahead, &moving),
+            load_priority(behind, &moving)
+        );
+
+        // with several scanners, the best priority counts
+        let both = [looking[0], moving[0]];
+        assert_eq!(load_priority(ahead, &both), load_priority(ahead, &looking));
+        assert_eq!(load_priority(right, &both), load_priority(right, &moving));
+    }
+
+    fn scanner_at(chunk_pos: IVec3) -> (Scanner, GlobalTransform) {
+        let translation = (chunk_pos * CHUNK_SIZE as i32 + CHUNK_SIZE as i32 / 2).as_vec3();
+        (
+            Scanner::new(1),
+            GlobalTransform::from_translation(translation),
+        )
+    }
+
+    #[test]
+    fn scanners_keep_each_others_chunks() {
+        let mut app = App::new();
+        let mut voxel_engine = VoxelEngine::default();
+        for z in -3..=3 {
+            for y in -3..=3 {
+                for x in -3..=15 {
+                    let chunk = Arc::new(ChunkData::filled(BlockType::Air));
+                    voxel_engine.world_data.insert(IVec3::new(x, y, z), chunk);
+                }
+            }
+        }
+        app.insert_resource(voxel_engine);
+        app.add_plugins(ScannerPlugin);
+        let a = app.world.spawn(scanner_at(IVec3::ZERO)).id();
+        let b = app.world.spawn(scanner_at(IVec3::X)).id();
+        app.update();
+        assert!(app
+            .world
+            .resource::<VoxelEngine>()
+            .unload_data_queue
+            .is_empty());
+
+        // both scanners stay put, a second update must not stall or unload anything
+        app.update();
+        assert!(app
+            .world
+            .resource::<VoxelEngine>()
+            .unload_data_queue
+            .is_empty());
+
+        *app.world.get_mut::<GlobalTransform>(a).unwrap() = scanner_at(IVec3::X * 10).1;
+        app.update();
+        let b_area = area(IVec3::X, 2);
+        let unloaded =
+            std::mem::take(&mut app.world.resource_mut::<VoxelEngine>().unload_data_queue);
+        assert!(!unloaded.is_empty());
+        assert!(unloaded.iter().all(|p| !b_area.contains(p)));
+
+        app.world.despawn(b);
+        app.update();
+        let unloaded = &app.world.resource::<VoxelEngine>().unload_data_queue;
+        assert_eq!(unloaded.len(), b_area.len());
+        assert!(unloaded.iter().all(|p| b_area.contains(p)));
+    }
+}
@@ -0,0 +1,635 @@
+use std::sync::OnceLock;
+
+use bevy::prelude::*;
+
+use crate::{
+    chunk_mesh::{ChunkMesh, MaterialWeights, SmoothMesh},
+    chunks_refs::ChunksRefs,
+    constants::CHUNK_SIZE_I32,
+    fluid::append_fluid_vertices,
+    lod::Lod,
+    utils::{generate_indices, index_to_ivec3_bounds, vec3_to_index},
+    voxel::MESHABLE_BLOCK_TYPES,
+};
+
+// marching cubes over a density field sampled at voxel centers.
+// cells span the voxel centers 0..=CHUNK_SIZE, the last layer reaches into the neighbour chunks
+// through ChunksRefs, so the cells of two chunks meet at the same samples and their seams match.
+// lower lods sample every jump_index voxels, the last cell is cut short at CHUNK_SIZE
+// when the jump doesn't divide it.
+
+/// densities above this are inside the surface. just under 0.5 so a lone voxel
+/// (density 0.5) keeps a small surface, flat ground lands just above its block faces
+pub const ISO_LEVEL: f32 = 0.45;
+
+// a voxel and its 6 face neighbours, with the density weight of the voxel and of each neighbour
+const KERNEL: [IVec3; 7] = [
+    IVec3::ZERO,
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+const CENTER_WEIGHT: f32 = 0.5;
+const NEIGHBOUR_WEIGHT: f32 = 0.5 / 6.0;
+
+// samples -1..=CHUNK_SIZE + 1, one past the cell corners on each side for gradients
+const FIELD_SIZE: i32 = CHUNK_SIZE_I32 + 3;
+// cell corners 0..=CHUNK_SIZE
+const LATTICE_SIZE: i32 = CHUNK_SIZE_I32 + 1;
+
+// cell corner i sits at (i & 1, i >> 1 & 1, i >> 2 & 1)
+#[inline]
+fn corner_offset(corner: usize) -> IVec3 {
+    IVec3::new(
+        (corner & 1) as i32,
+        (corner >> 1 & 1) as i32,
+        (corner >> 2 & 1) as i32,
+    )
+}
+
+// cell edges as (corner, axis), running from the corner along the axis
+const EDGES: [(usize, usize); 12] = [
+    (0, 0),
+    (2, 0),
+    (4, 0),
+    (6, 0),
+    (0, 1),
+    (1, 1),
+    (4, 1),
+    (5, 1),
+    (0, 2),
+    (1, 2),
+    (2, 2),
+    (3, 2),
+];
+
+fn edge_between(a: usize, b: usize) -> usize {
+    let corner = a.min(b);
+    let axis = (a ^ b).trailing_zeros() as usize;
+    EDGES.iter().position(|e| *e == (corner, axis)).unwrap()
+}
+
+/// triangles of each cell configuration (bit i set when corner i is inside) as EDGES indices,
+/// wound counter clockwise seen from outside
+pub fn triangle_table() -> &'static [Vec<[u8; 3]>] {
+    static TABLE: OnceLock<Vec<Vec<[u8; 3]>>> = OnceLock::new();
+    TABLE.get_or_init(|| (0..=255).map(cell_triangles).collect())
+}
+
+// connect two crossed edges on a cell face
+fn link(links: &mut [[usize; 2]; 12], a: usize, b: usize) {
+    for (from, to) in [(a, b), (b, a)] {
+        let slot = links[from].iter().position(|l| *l == usize::MAX).unwrap();
+        links[from][slot] = to;
+    }
+}
+
+// the surface of a cell, from the segments it leaves on the cell faces. cells sharing a face
+// see the same corners on it, so both pick the same segments
+fn cell_triangles(config: u8) -> Vec<[u8; 3]> {
+    let inside = |corner: usize| config >> corner & 1 == 1;
+    // every crossed edge borders 2 faces, so links to 2 other crossed edges
+    let mut links = [[usize::MAX; 2]; 12];
+    for axis in 0..3 {
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        for side in 0..2 {
+            let face =
+                [(0, 0), (1, 0), (1, 1), (0, 1)].map(|(a, b)| side << axis | a << u | b << v);
+            let crossed: Vec<usize> = (0..4)
+                .filter(|k| inside(face[*k]) != inside(face[(k + 1) % 4]))
+                .map(|k| edge_between(face[k], face[(k + 1) % 4]))
+                .collect();
+            match crossed.len() {
+                2 => link(&mut links, crossed[0], crossed[1]),
+                4 => {
+                    // diagonal corners inside, cut off each of them on its own
+                    for k in (0..4).filter(|k| inside(face[*k])) {
+                        let before = edge_between(face[(k + 3) % 4], face[k]);
+                        let after = edge_between(face[k], face[(k + 1) % 4]);
+                        link(&mut links, before, after);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let midpoint = |edge: usize| {
+        let (corner, axis) = EDGES[edge];
+        corner_offset(corner).as_vec3() + Vec3::AXES[axis] * 0.5
+    };
+    // along the edge, pointing from its inside corner to its outside one
+    let edge_outward = |edge: usize| {
+        let (corner, axis) = EDGES[edge];
+        match inside(corner) {
+            true => Vec3::AXES[axis],
+            false => -Vec3::AXES[axis],
+        }
+    };
+    let mut visited = [false; 12];
+    let mut triangles = vec![];
+    for start in 0..12 {
+        if visited[start] || links[start][0] == usize::MAX {
+            continue;
+        }
+        // walk the loop of linked edges
+        let mut ring = vec![start];
+        visited[start] = true;
+        let (mut previous, mut current) = (start, links[start][0]);
+        while current != start {
+            visited[current] = true;
+            ring.push(current);
+            let next = match links[current][0] == previous {
+                true => links[current][1],
+                false => links[current][0],
+            };
+            (previous, current) = (current, next);
+        }
+
+        // face the loop away from the inside corners
+        let outward: Vec3 = ring.iter().map(|edge| edge_outward(*edge)).sum();
+        let normal: Vec3 = (0..ring.len())
+            .map(|i| midpoint(ring[i]).cross(midpoint(ring[(i + 1) % ring.len()])))
+            .sum();
+        if normal.dot(outward) < 0.0 {
+            ring.reverse();
+        }
+        // fan from a corner of the loop that doesn't fold any triangle back on a bent loop
+        let (ring, len) = (&ring, ring.len());
+        let fan = |origin: usize| {
+            (1..len - 1).map(move |i| [0, i, i + 1].map(|k| ring[(origin + k) % len]))
+        };
+        let faces_out = |[a, b, c]: [usize; 3]| {
+            let outward = edge_outward(a) + edge_outward(b) + edge_outward(c);
+            let (a, b, c) = (midpoint(a), midpoint(b), midpoint(c));
+            (b - a).cross(c - a).dot(outward) > 0.0
+        };
+        let origin = (0..len)
+            .find(|origin| fan(*origin).all(faces_out))
+            .unwrap_or(0);
+        triangles.extend(fan(origin).map(|triangle| triangle.map(|edge| edge as u8)));
+    }
+    triangles
+}
+
+// solid voxels -2..=CHUNK_SIZE + 2, the field samples and the kernel around them
+const SOLID_SIZE: i32 = FIELD_SIZE + 2;
+
+// solid voxels around the middle chunk, from the occupancy columns of the chunks they're in
+fn solid_voxels(chunks_refs: &ChunksRefs) -> Vec<bool> {
+    // (neighbour offset 0-2, local coordinate) of a coordinate relative to the middle chunk
+    let split = |v: i32| {
+        let v = v + CHUNK_SIZE_I32;
+        (v / CHUNK_SIZE_I32, (v % CHUNK_SIZE_I32) as u32)
+    };
+    let mut solid = vec![false; (SOLID_SIZE * SOLID_SIZE * SOLID_SIZE) as usize];
+    for z in 0..SOLID_SIZE {
+        for x in 0..SOLID_SIZE {
+            let (chunk_z, local_z) = split(z - 2);
+            let (chunk_x, local_x) = split(x - 2);
+            let mut column = (0, 0);
+            for y in 0..SOLID_SIZE {
+                let (chunk_y, local_y) = split(y - 2);
+                // columns along y are indexed [z][x], see ChunkOccupancy::cols
+                if y == 0 || local_y == 0 {
+                    let chunk = vec3_to_index(IVec3::new(chunk_x, chunk_y, chunk_z), 3);
+                    let chunk = &chunks_refs.chunks[chunk];
+                    column = (
+                        chunk_y,
+                        chunk.solid_column(0, local_z as usize, local_x as usize),
+                    );
+                }
+                solid[vec3_to_index(IVec3::new(x, y, z), SOLID_SIZE)] =
+                    column.1 >> local_y & 1 == 1;
+            }
+        }
+    }
+    solid
+}
+
+// densities of the voxels around the middle chunk, material weights are looked up
+// only for the samples next to the surface
+struct DensityField<'a> {
+    chunks_refs: &'a ChunksRefs,
+    densities: Vec<f32>,
+}
+
+impl<'a> DensityField<'a> {
+    fn new(chunks_refs: &'a ChunksRefs) -> Self {
+        let solid = solid_voxels(chunks_refs);
+        let len = (FIELD_SIZE * FIELD_SIZE * FIELD_SIZE) as usize;
+        let mut densities = vec![0.0; len];
+        for (i, density) in densities.iter_mut().enumerate() {
+            // samples start at -1, solid voxels at -2
+            let pos = index_to_ivec3_bounds(i as i32, FIELD_SIZE) + IVec3::ONE;
+            // solidity of the voxel, smoothed with its face neighbours
+            for offset in KERNEL {
+                if !solid[vec3_to_index(pos + offset, SOLID_SIZE)] {
+                    continue;
+                }
+                *density += match offset == IVec3::ZERO {
+                    true => CENTER_WEIGHT,
+                    false => NEIGHBOUR_WEIGHT,
+                };
+            }
+        }
+        Self {
+            chunks_refs,
+            densities,
+        }
+    }
+
+    // material weights of the sample at pos, they add up to its density
+    fn weights(&self, pos: IVec3) -> MaterialWeights {
+        let mut weights = [0.0; MESHABLE_BLOCK_TYPES.len()];
+        for offset in KERNEL {
+            let block_type = self.chunks_refs.get_block(pos + offset).block_type;
+            let Some(material) = MESHABLE_BLOCK_TYPES.iter().position(|b| *b == block_type) else {
+                continue;
+            };
+            weights[material] += match offset == IVec3::ZERO {
+                true => CENTER_WEIGHT,
+                false => NEIGHBOUR_WEIGHT,
+            };
+        }
+        weights
+    }
+
+    #[inline]
+    fn index(pos: IVec3) -> usize {
+        vec3_to_index(pos + IVec3::ONE, FIELD_SIZE)
+    }
+
+    #[inline]
+    fn density(&self, pos: IVec3) -> f32 {
+        self.densities[Self::index(pos)]
+    }
+
+    // away from the solids, the density increases towards them
+    fn normal(&self, pos: IVec3) -> Vec3 {
+        let gradient = Vec3::from_array(
+            IVec3::AXES.map(|axis| self.density(pos + axis) - self.density(pos - axis)),
+        );
+        -gradient
+    }
+
+    // vertex where the surface crosses the cell edge from pos to other, along one axis
+    fn push_vertex(&self, mesh: &mut SmoothMesh, pos: IVec3, other: IVec3) {
+        let (a, b) = (self.density(pos), self.density(other));
+        let t = ((ISO_LEVEL - a) / (b - a)).clamp(0.0, 1.0);
+        let edge = (other - pos).as_vec3();
+        let outward = match a > b {
+            true => edge.normalize(),
+            false => -edge.normalize(),
+        };
+        let normal = self
+            .normal(pos)
+            .lerp(self.normal(other), t)
+            .try_normalize()
+            .unwrap_or(outward);
+        let (wa, wb) = (self.weights(pos), self.weights(other));
+        let weights: MaterialWeights = std::array::from_fn(|i| wa[i] + (wb[i] - wa[i]) * t);
+        let sum: f32 = weights.iter().sum();
+
+        // voxel centers sit half a voxel into the voxel
+        let position = pos.as_vec3() + 0.5 + edge * t;
+        mesh.positions.push(position.to_array());
+        mesh.normals.push(normal.to_array());
+        mesh.weights.push(weights.map(|w| w / sum));
+    }
+}
+
+/// smooth triangle mesh of the middle chunk, see ChunkMesh::smooth
+/// fluids are meshed as u32 vertices at full detail, like the blocky meshers do
+pub fn build_chunk_mesh(chunks_refs: &ChunksRefs, lod: Lod) -> Option<ChunkMesh> {
+    // early exit, nothing to smooth
+    if chunks_refs.is_all_voxels_same() {
+        return None;
+    }
+    let field = DensityField::new(chunks_refs);
+    let table = triangle_table();
+    let mut smooth = SmoothMesh::default();
+    let jump = lod.jump_index();
+    let cells = (CHUNK_SIZE_I32 + jump - 1) / jump;
+    // voxel center sampled by a lattice point
+    let sample = |lattice: IVec3| (lattice * jump).min(IVec3::splat(CHUNK_SIZE_I32));
+    // vertex of every lattice edge, shared by the cells around it
+    let mut edge_vertices =
+        vec![u32::MAX; (LATTICE_SIZE * LATTICE_SIZE * LATTICE_SIZE) as usize * 3];
+    for i in 0..cells * cells * cells {
+        let cell = index_to_ivec3_bounds(i, cells);
+        let config = (0..8).fold(0, |config, corner| {
+            let inside = field.density(sample(cell + corner_offset(corner))) > ISO_LEVEL;
+            config | (inside as usize) << corner
+        });
+        for triangle in table[config].iter() {
+            let indices = triangle.map(|edge| {
+                let (corner, axis) = EDGES[edge as usize];
+                let pos = cell + corner_offset(corner);
+                let key = vec3_to_index(pos, LATTICE_SIZE) * 3 + axis;
+                if edge_vertices[key] == u32::MAX {
+                    edge_vertices[key] = smooth.positions.len() as u32;
+                    let other = sample(pos + IVec3::AXES[axis]);
+                    field.push_vertex(&mut smooth, sample(pos), other);
+                }
+                edge_vertices[key]
+            });
+            smooth.indices.extend(indices);
+        }
+    }
+    // fluids aren't part of the density field, they keep their blocky faces
+    let mut vertices = vec![];
+    append_fluid_vertices(chunks_refs, &mut vertices);
+    if smooth.indices.is_empty() && vertices.is_empty() {
+        return None;
+    }
+    Some(ChunkMesh {
+        indices: generate_indices(vertices.len()),
+        vertices,
+        smooth: (!smooth.indices.is_empty()).then_some(smooth),
+        ..default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bevy::utils::HashMap;
+
+    use super::*;
+    use crate::{
+        chunk::ChunkData,
+        test_utils::chunk_from_fn,
+        voxel::{BlockData, BlockType},
+    };
+
+    fn smooth_mesh(chunks_refs: &ChunksRefs) -> Option<SmoothMesh> {
+        smooth_mesh_lod(chunks_refs, Lod::L32)
+    }
+
+    fn smooth_mesh_lod(chunks_refs: &ChunksRefs, lod: Lod) -> Option<SmoothMesh> {
+        build_chunk_mesh(chunks_refs, lod).and_then(|m| m.smooth)
+    }
+
+    fn generated_world(min: IVec3, max: IVec3) -> HashMap<IVec3, Arc<ChunkData>> {
+        let mut world_data = HashMap::new();
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    let pos = IVec3::new(x, y, z);
+                    world_data.insert(pos, Arc::new(ChunkData::generate(pos)));
+                }
+            }
+        }
+        world_data
+    }
+
+    // edges with both ends on one of the planes the chunk's surface is cut off at
+    fn on_chunk_border(a: [f32; 3], b: [f32; 3]) -> bool {
+        let border = |v: f32| v == 0.5 || v == CHUNK_SIZE_I32 as f32 + 0.5;
+        (0..3).any(|i| a[i] == b[i] && border(a[i]))
+    }
+
+    // every inner edge is shared by exactly 2 triangles, running opposite ways
+    fn assert_closed(mesh: &SmoothMesh) {
+        let mut edges = HashMap::new();
+        for triangle in mesh.indices.chunks(3) {
+            for i in 0..3 {
+                let edge = (triangle[i], triangle[(i + 1) % 3]);
+                assert!(edges.insert(edge, ()).is_none(), "edge {edge:?} used twice");
+            }
+        }
+        for (a, b) in edges.keys() {
+            let (pa, pb) = (mesh.positions[*a as usize], mesh.positions[*b as usize]);
+            assert!(
+                edges.contains_key(&(*b, *a)) || on_chunk_border(pa, pb),
+                "open edge {pa:?} {pb:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn triangle_table_uses_the_crossed_edges() {
+        let table = triangle_table();
+        assert!(table[0].is_empty() && table[255].is_empty());
+        for (config, triangles) in table.iter().enumerate().take(255).skip(1) {
+            let inside = |corner: usize| config >> corner & 1 == 1;
+            let crossed: Vec<u8> = (0..12u8)
+                .filter(|e| {
+                    let (corner, axis) = EDGES[*e as usize];
+                    inside(corner) != inside(corner | 1 << axis)
+                })
+                .collect();
+            let mut used: Vec<u8> = triangles.iter().flatten().copied().collect();
+            used.sort();
+            used.dedup();
+            assert_eq!(used, crossed, "config {config:08b}");
+
+            // triangles face away from the inside corners of their edges
+            for triangle in triangles.iter() {
+                let [a, b, c] = triangle.map(|e| {
+                    let (corner, axis) = EDGES[e as usize];
+                    corner_offset(corner).as_vec3() + Vec3::AXES[axis] * 0.5
+                });
+                let outward: Vec3 = triangle
+                    .iter()
+                    .map(|e| {
+                        let (corner, axis) = EDGES[*e as usize];
+                        Vec3::AXES[axis] * if inside(corner) { 1.0 } else { -1.0 }
+                    })
+                    .sum();
+                assert!(
+                    (b - a).cross(c - a).dot(outward) > 0.0,
+                    "config {config:08b}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn generated_surfaces_are_closed() {
+        let mut meshed = 0;
+        for seed in 0..32 {
+            let Some(mesh) = smooth_mesh(&ChunksRefs::make_dummy_chunk_refs(seed)) else {
+                continue;
+            };
+            meshed += 1;
+            assert_closed(&mesh);
+            let len = mesh.positions.len();
+            assert!(mesh.normals.len() == len && mesh.weights.len() == len);
+            for (normal, weights) in mesh.normals.iter().zip(mesh.weights.iter()) {
+                assert!((Vec3::from_array(*normal).length() - 1.0).abs() < 1e-4);
+                assert!((weights.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+            }
+            for p in mesh.positions.iter().flatten() {
+                assert!((0.5..=CHUNK_SIZE_I32 as f32 + 0.5).contains(p), "{p}");
+            }
+        }
+        assert!(meshed > 0);
+    }
+
+    #[test]
+    fn seams_match_between_chunks() {
+        let world_data = generated_world(IVec3::new(-2, -2, -2), IVec3::new(3, 2, 3));
+        let size = CHUNK_SIZE_I32 as f32;
+        let mut seams = 0;
+        let middles = [
+            IVec3::new(0, -1, 0),
+            IVec3::new(1, 0, -1),
+            IVec3::new(-1, 0, 1),
+        ];
+        for (middle, lod) in middles
+            .into_iter()
+            .flat_map(|m| [(m, Lod::L32), (m, Lod::L8)])
+        {
+            let chunks_refs = ChunksRefs::try_new(&world_data, middle).unwrap();
+            let mesh = smooth_mesh_lod(&chunks_refs, lod).unwrap_or_default();
+            for axis in 0..3 {
+                let next = middle + IVec3::AXES[axis];
+                let chunks_refs = ChunksRefs::try_new(&world_data, next).unwrap();
+                let next_mesh = smooth_mesh_lod(&chunks_refs, lod).unwrap_or_default();
+                // vertices on the shared plane, in the middle chunk's space
+                let seam = |mesh: &SmoothMesh, plane: f32, offset: f32| {
+                    let mut vertices: Vec<_> = (0..mesh.positions.len())
+                        .filter(|i| mesh.positions[*i][axis] == plane)
+                        .map(|i| {
+                            let mut pos = mesh.positions[i];
+                            pos[axis] += offset;
+                            let mut normal = mesh.normals[i];
+                            normal.iter_mut().for_each(|n| *n = (*n * 1e4).round());
+                            (pos.map(f32::to_bits), normal.map(|n| n as i32))
+                        })
+                        .collect();
+                    vertices.sort();
+                    vertices
+                };
+                let a = seam(&mesh, size + 0.5, 0.0);
+                let b = seam(&next_mesh, 0.5, size);
+                assert_eq!(a, b, "{middle} axis {axis} jump {}", lod.jump_index());
+                seams += !a.is_empty() as usize;
+            }
+        }
+        assert!(seams > 0);
+    }
+
+    // dirt below height in the middle layer of chunks, and everywhere in the layer under it
+    fn flat_ground(height: i32) -> ChunksRefs {
+        let ground = Arc::new(chunk_from_fn(|pos| match pos.y < height {
+            true => BlockType::Dirt.into(),
+            false => BlockData::default(),
+        }));
+        let mut chunks_refs = ChunksRefs::uniform(BlockType::Air);
+        for (i, chunk) in chunks_refs.chunks.iter_mut().enumerate() {
+            match index_to_ivec3_bounds(i as i32, 3).y {
+                0 => *chunk = Arc::new(ChunkData::filled(BlockType::Dirt)),
+                1 => *chunk = ground.clone(),
+                _ => {}
+            }
+        }
+        chunks_refs
+    }
+
+    #[test]
+    fn flat_ground_stays_flat() {
+        let height = 10;
+        let mesh = smooth_mesh(&flat_ground(height)).unwrap();
+        assert_closed(&mesh);
+        let y = mesh.positions[0][1];
+        assert!((y - height as f32).abs() < 0.1, "{y}");
+        for (pos, normal) in mesh.positions.iter().zip(mesh.normals.iter()) {
+            assert_eq!(pos[1], y);
+            assert_eq!(*normal, [0.0, 1.0, 0.0]);
+        }
+        let dirt = MESHABLE_BLOCK_TYPES
+            .iter()
+            .position(|b| *b == BlockType::Dirt)
+            .unwrap();
+        assert!(mesh.weights.iter().all(|w| w[dirt] == 1.0));
+    }
+
+    #[test]
+    fn lower_lods_sample_fewer_cells() {
+        let height = 10;
+        let chunks_refs = flat_ground(height);
+        let mut vertices = smooth_mesh(&chunks_refs).unwrap().positions.len();
+        for lod in [Lod::L16, Lod::L8, Lod::L4, Lod::L2] {
+            let mesh = smooth_mesh_lod(&chunks_refs, lod).unwrap();
+            assert_closed(&mesh);
+            // the surface lands between the samples around it
+            let y = mesh.positions[0][1];
+            let jump = lod.jump_index() as f32;
+            assert!((y - height as f32).abs() < jump, "{y} jump {jump}");
+            assert!(mesh.positions.iter().all(|pos| pos[1] == y));
+            assert!(mesh.positions.len() < vertices);
+            vertices = mesh.positions.len();
+        }
+    }
+
+    #[test]
+    fn lone_voxel_keeps_a_closed_surface() {
+        let center = IVec3::new(5, 6, 7);
+        let chunks_refs = ChunksRefs::with_middle(chunk_from_fn(|pos| match pos == center {
+            true => BlockType::Sand.into(),
+            false => BlockData::default(),
+        }));
+        let mesh = smooth_mesh(&chunks_refs).expect("lone voxels aren't smoothed away");
+        assert_closed(&mesh);
+        for pos in mesh.positions.iter() {
+            let offset = Vec3::from_array(*pos) - (center.as_vec3() + 0.5);
+            assert!(offset.length() < 0.5, "{offset}");
+        }
+    }
+
+    #[test]
+    fn fluids_keep_blocky_faces() {
+        let water = IVec3::new(3, 4, 5);
+        let only_water = ChunksRefs::with_middle(chunk_from_fn(|pos| match pos == water {
+            true => BlockType::Water.into(),
+            false => BlockData::default(),
+        }));
+        let mesh = build_chunk_mesh(&only_water, Lod::L32).expect("water is drawn");
+        assert!(mesh.smooth.is_none());
+        assert_eq!(mesh.vertices.len(), 6 * 4);
+        assert_eq!(mesh.indices, generate_indices(mesh.vertices.len()));
+
+        // water resting on a sand floor, both are drawn
+        let pool = ChunksRefs::with_middle(chunk_from_fn(|pos| match pos.y {
+            0 => BlockType::Sand.into(),
+            1 => BlockType::Water.into(),
+            _ => BlockData::default(),
+        }));
+        let mesh = build_chunk_mesh(&pool, Lod::L32).unwrap();
+        assert!(mesh.smooth.is_some());
+        assert!(mesh.vertices.iter().all(|v| v >> 24 & 1 == 1));
+        assert!(!mesh.vertices.is_empty());
+    }
+
+    #[test]
+    fn densities_match_get_block() {
+        for seed in 0..8 {
+            let chunks_refs = ChunksRefs::make_dummy_chunk_refs(seed);
+            let field = DensityField::new(&chunks_refs);
+            for i in 0..FIELD_SIZE * FIELD_SIZE * FIELD_SIZE {
+                let pos = index_to_ivec3_bounds(i, FIELD_SIZE) - IVec3::ONE;
+                let density: f32 = KERNEL
+                    .iter()
+                    .filter(|offset| chunks_refs.get_block(pos + **offset).block_type.is_solid())
+                    .map(|offset| match *offset == IVec3::ZERO {
+                        true => CENTER_WEIGHT,
+                        false => NEIGHBOUR_WEIGHT,
+                    })
+                    .sum();
+                assert!(
+                    (field.density(pos) - density).abs() < 1e-6,
+                    "seed {seed} {pos}"
+                );
+                let weights = field.weights(pos).iter().sum::<f32>();
+                assert!((weights - density).abs() < 1e-6, "seed {seed} {pos}");
+            }
+        }
+    }
+}
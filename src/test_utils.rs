@@ -0,0 +1,43 @@
+use std::{ops::RangeInclusive, sync::Arc};
+
+use bevy::{math::IVec3, utils::HashMap};
+
+use crate::{
+    chunk::ChunkData,
+    constants::{CHUNK_SIZE3, CHUNK_SIZE_I32},
+    utils::{index_to_ivec3, vec3_to_index, world_voxel_to_chunk},
+    voxel::{BlockData, BlockType},
+};
+
+/// chunk with the voxel of every local position
+pub fn chunk_from_fn(voxel: impl Fn(IVec3) -> BlockData) -> ChunkData {
+    ChunkData::new(
+        (0..CHUNK_SIZE3 as i32)
+            .map(|i| voxel(index_to_ivec3(i)))
+            .collect(),
+    )
+}
+
+/// chunks x_range by -1..=1 by -1..=1, dirt below y = 0 and air above
+pub fn flat_world(x_range: RangeInclusive<i32>) -> HashMap<IVec3, Arc<ChunkData>> {
+    let mut world_data = HashMap::new();
+    for z in -1..=1 {
+        for y in -1..=1 {
+            for x in x_range.clone() {
+                let block_type = match y < 0 {
+                    true => BlockType::Dirt,
+                    false => BlockType::Air,
+                };
+                world_data.insert(IVec3::new(x, y, z), Arc::new(ChunkData::filled(block_type)));
+            }
+        }
+    }
+    world_data
+}
+
+/// replace the voxel at a world position, panics if its chunk isn't loaded
+pub fn set_block(world_data: &mut HashMap<IVec3, Arc<ChunkData>>, pos: IVec3, block: BlockData) {
+    let (chunk_pos, local_pos) = world_voxel_to_chunk(pos);
+    let chunk = Arc::make_mut(world_data.get_mut(&chunk_pos).unwrap());
+    chunk.set_block(vec3_to_index(local_pos, CHUNK_SIZE_I32), block);
+}
@@ -1,10 +1,12 @@
 use bevy::prelude::*;
 
+use crate::constants::{CHUNK_SIZE, CHUNK_SIZE2_I32, CHUNK_SIZE_I32};
+
 #[inline]
 pub fn index_to_ivec3(i: i32) -> IVec3 {
-    let x = i % 32;
-    let y = (i / 32) % 32;
-    let z = i / (32 * 32);
+    let x = i % CHUNK_SIZE_I32;
+    let y = (i / CHUNK_SIZE_I32) % CHUNK_SIZE_I32;
+    let z = i / CHUNK_SIZE2_I32;
     IVec3::new(x, y, z)
 }
 
@@ -26,13 +28,13 @@ pub fn index_to_ivec3_bounds_reverse(i: i32, bounds: i32) -> IVec3 {
 
 #[inline]
 pub fn is_on_edge(pos: IVec3) -> bool {
-    if pos.x == 0 || pos.x == 32 {
+    if pos.x == 0 || pos.x == CHUNK_SIZE_I32 {
         return true;
     }
-    if pos.y == 0 || pos.y == 32 {
+    if pos.y == 0 || pos.y == CHUNK_SIZE_I32 {
         return true;
     }
-    if pos.z == 0 || pos.z == 32 {
+    if pos.z == 0 || pos.z == CHUNK_SIZE_I32 {
         return true;
     }
     false
@@ -44,17 +46,17 @@ pub fn get_edging_chunk(pos: IVec3) -> Option<IVec3> {
     let mut chunk_dir = IVec3::ZERO;
     if pos.x == 0 {
         chunk_dir.x = -1;
-    } else if pos.x == 31 {
+    } else if pos.x == CHUNK_SIZE_I32 - 1 {
         chunk_dir.x = 1;
     }
     if pos.y == 0 {
         chunk_dir.y = -1;
-    } else if pos.y == 31 {
+    } else if pos.y == CHUNK_SIZE_I32 - 1 {
         chunk_dir.y = 1;
     }
     if pos.z == 0 {
         chunk_dir.z = -1;
-    } else if pos.z == 31 {
+    } else if pos.z == CHUNK_SIZE_I32 - 1 {
         chunk_dir.z = 1;
     }
     if chunk_dir == IVec3::ZERO {
@@ -66,6 +68,9 @@ pub fn get_edging_chunk(pos: IVec3) -> Option<IVec3> {
 
 // pos 18 bits, ao 3 bits, normal 4 bits
 // 18-21-25-   left 32-25 = 7
+// positions run from 0 to CHUNK_SIZE on every axis, 6 bits each
+const _: () = assert!(CHUNK_SIZE < 1 << 6);
+
 #[inline]
 pub fn make_vertex_u32(
     // position: [i32; 3], /*, normal: i32, color: Color, texture_id: u32*/
@@ -84,9 +89,24 @@ pub fn make_vertex_u32(
     // | (texture_id) << 21u32
 }
 
+/// fluid vertices reuse the ao bits to lower the vertex by drop / 8 of a voxel,
+/// the shader tells them apart by bit 24
+#[inline]
+pub fn make_fluid_vertex_u32(pos: IVec3, drop: u32, normal: u32, block_type: u32) -> u32 {
+    make_vertex_u32(pos, drop, normal, block_type) | 1u32 << 24u32
+}
+
 #[inline]
 pub fn world_to_chunk(pos: Vec3) -> IVec3 {
-    ((pos - Vec3::splat(16.0)) * (1.0 / 32.0)).as_ivec3()
+    let size = CHUNK_SIZE_I32 as f32;
+    ((pos - Vec3::splat(size / 2.0)) * (1.0 / size)).as_ivec3()
+}
+
+/// split a world voxel position into its chunk position and the local position inside that chunk
+#[inline]
+pub fn world_voxel_to_chunk(pos: IVec3) -> (IVec3, IVec3) {
+    let size = IVec3::splat(CHUNK_SIZE_I32);
+    (pos.div_euclid(size), pos.rem_euclid(size))
 }
 
 ///! generate a vec of indices
@@ -109,13 +129,14 @@ pub fn generate_indices(vertex_count: usize) -> Vec<u32> {
 
 #[test]
 fn index_functions() {
-    for z in 0..32 {
-        for y in 0..32 {
-            for x in 0..32 {
+    for z in 0..CHUNK_SIZE_I32 {
+        for y in 0..CHUNK_SIZE_I32 {
+            for x in 0..CHUNK_SIZE_I32 {
                 let pos = IVec3::new(x, y, z);
-                let index = vec3_to_index(pos, 32);
-                let from_index = index_to_ivec3_bounds(index as i32, 32);
+                let index = vec3_to_index(pos, CHUNK_SIZE_I32);
+                let from_index = index_to_ivec3_bounds(index as i32, CHUNK_SIZE_I32);
                 assert_eq!(pos, from_index);
+                assert_eq!(pos, index_to_ivec3(index as i32));
             }
         }
     }
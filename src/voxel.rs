@@ -1,13 +1,25 @@
-#[repr(u32)]
-#[derive(Eq, PartialEq, Default, Copy, Clone, Debug)]
+#[repr(u8)]
+#[derive(Eq, PartialEq, Default, Copy, Clone, Debug, Hash)]
 pub enum BlockType {
     #[default]
     Air,
     Grass,
     Dirt,
+    Water,
+    Lava,
+    Sand,
+    Gravel,
 }
 
-pub const MESHABLE_BLOCK_TYPES: &'static [BlockType] = &[BlockType::Grass, BlockType::Dirt];
+pub const MESHABLE_BLOCK_TYPES: &'static [BlockType] = &[
+    BlockType::Grass,
+    BlockType::Dirt,
+    BlockType::Sand,
+    BlockType::Gravel,
+];
+
+/// fluid level of a source block, flowing fluids use 1..FLUID_SOURCE_LEVEL
+pub const FLUID_SOURCE_LEVEL: u8 = 8;
 
 impl BlockType {
     pub fn is_solid(&self) -> bool {
@@ -15,14 +27,53 @@ impl BlockType {
             BlockType::Air => false,
             BlockType::Grass => true,
             BlockType::Dirt => true,
+            BlockType::Water => false,
+            BlockType::Lava => false,
+            BlockType::Sand => true,
+            BlockType::Gravel => true,
         }
     }
     pub fn is_air(&self) -> bool {
         !self.is_solid()
     }
+    /// neither solid nor fluid, agents can stand in it
+    pub fn is_passable(&self) -> bool {
+        !self.is_solid() && !self.is_fluid()
+    }
+    pub fn is_fluid(&self) -> bool {
+        matches!(self, BlockType::Water | BlockType::Lava)
+    }
+    /// falls down when the block below it isn't solid
+    pub fn has_gravity(&self) -> bool {
+        matches!(self, BlockType::Sand | BlockType::Gravel)
+    }
 }
 
-#[derive(Default, Copy, Clone, Debug)]
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq)]
 pub struct BlockData {
     pub block_type: BlockType,
+    /// only used by fluids, 0 for every other block
+    pub fluid_level: u8,
+}
+
+impl BlockData {
+    pub fn fluid(block_type: BlockType, fluid_level: u8) -> Self {
+        Self {
+            block_type,
+            fluid_level,
+        }
+    }
+}
+
+impl From<BlockType> for BlockData {
+    fn from(block_type: BlockType) -> Self {
+        let fluid_level = match block_type.is_fluid() {
+            true => FLUID_SOURCE_LEVEL,
+            false => 0,
+        };
+        Self {
+            block_type,
+            fluid_level,
+        }
+    }
 }
@@ -1,13 +1,13 @@
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use bevy::{
     asset::LoadState,
     diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic},
     prelude::*,
-    render::{
-        mesh::Indices, primitives::Aabb, render_asset::RenderAssetUsages,
-        render_resource::PrimitiveTopology,
-    },
+    render::primitives::{Aabb, Frustum},
     tasks::{block_on, AsyncComputeTaskPool, Task},
     utils::{HashMap, HashSet},
 };
@@ -19,21 +19,29 @@ use crate::{
     chunks_refs::ChunksRefs,
     constants::CHUNK_SIZE_I32,
     lod::Lod,
-    rendering::{GlobalChunkMaterial, ATTRIBUTE_VOXEL},
-    scanner::Scanner,
-    utils::{get_edging_chunk, vec3_to_index},
-    voxel::{BlockData, BlockType},
+    mesher::{
+        BinaryGreedyMesher, CulledMesher, CulledMesherOptimized, GreedyMesher, MeshOptions, Mesher,
+        SmoothMesher,
+    },
+    rendering::{
+        packed_quad_mesh, smooth_chunk_mesh, voxel_chunk_mesh, ChunkQuadMaterial,
+        GlobalChunkMaterial, GlobalChunkQuadMaterial, GlobalSmoothChunkMaterial,
+    },
+    scanner::{load_priority, ChunkInterest, Scanner, ScannerView},
+    utils::{get_edging_chunk, vec3_to_index, world_to_chunk, world_voxel_to_chunk},
+    voxel::BlockData,
 };
 use futures_lite::future;
 
 pub struct VoxelEnginePlugin;
 
-pub const MAX_DATA_TASKS: usize = 64;
-pub const MAX_MESH_TASKS: usize = 32;
-
 impl Plugin for VoxelEnginePlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(VoxelEngine::default());
+        app.init_resource::<ChunkWorkBudget>();
+        app.init_resource::<ChunkWorkSpent>();
+        app.init_resource::<WastedChunkWork>();
+        app.add_event::<VoxelModified>();
         // app.add_systems(Update, (start_data_tasks, start_mesh_tasks));
         app.add_systems(PostUpdate, (start_data_tasks, start_mesh_tasks));
         // app.add_systems(PostUpdate, (join_data, join_mesh));
@@ -41,7 +49,11 @@ impl Plugin for VoxelEnginePlugin {
         app.add_systems(
             // PostUpdate,
             Update,
-            ((join_data, join_mesh), (unload_data, unload_mesh)).chain(),
+            (
+                (cancel_unwanted_tasks, join_data, join_mesh).chain(),
+                (unload_data, unload_mesh),
+            )
+                .chain(),
         );
         app.add_systems(Update, debug_inputs);
 
@@ -53,6 +65,8 @@ impl Plugin for VoxelEnginePlugin {
         app.register_diagnostic(Diagnostic::new(DIAG_VERTEX_COUNT));
         app.register_diagnostic(Diagnostic::new(DIAG_MESH_TASKS));
         app.register_diagnostic(Diagnostic::new(DIAG_DATA_TASKS));
+        app.register_diagnostic(Diagnostic::new(DIAG_CANCELLED_TASKS));
+        app.register_diagnostic(Diagnostic::new(DIAG_DISCARDED_TASKS));
         app.add_systems(Update, diagnostics_count);
     }
 }
@@ -60,18 +74,14 @@ impl Plugin for VoxelEnginePlugin {
 pub fn debug_inputs(
     keyboard_input: Res<ButtonInput<KeyCode>>,
     mut voxel_engine: ResMut<VoxelEngine>,
-    scanners: Query<(&GlobalTransform, &Scanner)>,
+    chunk_interest: Res<ChunkInterest>,
 ) {
     if keyboard_input.just_pressed(KeyCode::KeyR) {
         // swap meshing algorithm
-        use MeshingMethod as MM;
-        voxel_engine.meshing_method = match voxel_engine.meshing_method {
-            MM::VertexCulled => MM::BinaryGreedyMeshing,
-            MM::BinaryGreedyMeshing => MM::VertexCulled,
-        };
-        let (scanner_transform, scanner) = scanners.single();
+        voxel_engine.meshing_method = voxel_engine.meshing_method.next();
+        info!("meshing method: {:?}", voxel_engine.meshing_method);
         // unload all meshes
-        voxel_engine.unload_all_meshes(scanner, scanner_transform);
+        voxel_engine.unload_all_meshes(&chunk_interest);
     }
     if keyboard_input.just_pressed(KeyCode::KeyT) {
         // toggle rendering method
@@ -81,7 +91,36 @@ pub fn debug_inputs(
 #[derive(Debug, Reflect, Copy, Clone, Eq, PartialEq, Hash)]
 pub enum MeshingMethod {
     VertexCulled,
+    VertexCulledOptimized,
+    GreedyMeshing,
     BinaryGreedyMeshing,
+    MarchingCubes,
+}
+
+impl MeshingMethod {
+    pub const ALL: [MeshingMethod; 5] = [
+        MeshingMethod::VertexCulled,
+        MeshingMethod::VertexCulledOptimized,
+        MeshingMethod::GreedyMeshing,
+        MeshingMethod::BinaryGreedyMeshing,
+        MeshingMethod::MarchingCubes,
+    ];
+
+    pub fn mesher(self) -> &'static dyn Mesher {
+        match self {
+            MeshingMethod::VertexCulled => &CulledMesher,
+            MeshingMethod::VertexCulledOptimized => &CulledMesherOptimized,
+            MeshingMethod::GreedyMeshing => &GreedyMesher,
+            MeshingMethod::BinaryGreedyMeshing => &BinaryGreedyMesher,
+            MeshingMethod::MarchingCubes => &SmoothMesher,
+        }
+    }
+
+    /// the method after this one in ALL, wrapping around
+    pub fn next(self) -> MeshingMethod {
+        let i = Self::ALL.iter().position(|m| *m == self).unwrap();
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
 }
 
 ///! holds all voxel world data
@@ -100,9 +139,71 @@ pub struct VoxelEngine {
     pub lod: Lod,
     pub meshing_method: MeshingMethod,
     pub chunk_modifications: HashMap<IVec3, Vec<ChunkModification>>,
+    /// attach a ChunkCollisionMesh to chunk entities, see Mesher::supports_collision
+    pub build_collision_meshes: bool,
+    /// render chunks from packed quads with ChunkQuadMaterial, see Mesher::supports_packed_quads.
+    /// these chunks are not affected by the wireframe toggle
+    pub packed_quads: bool,
+}
+
+/// limits on how much chunk work is started and finished each frame
+#[derive(Resource, Debug, Clone)]
+pub struct ChunkWorkBudget {
+    /// data generation tasks running at the same time
+    pub max_data_tasks: usize,
+    /// mesh building tasks running at the same time
+    pub max_mesh_tasks: usize,
+    /// milliseconds per frame spent joining finished tasks, uploading meshes and spawning
+    /// chunk entities. finished tasks over budget are joined next frame
+    pub frame_budget_ms: f32,
+}
+
+impl Default for ChunkWorkBudget {
+    fn default() -> Self {
+        Self {
+            max_data_tasks: 64,
+            max_mesh_tasks: 32,
+            frame_budget_ms: 4.0,
+        }
+    }
+}
+
+impl ChunkWorkBudget {
+    pub fn frame_budget(&self) -> Duration {
+        Duration::from_secs_f32(self.frame_budget_ms.max(0.0) / 1000.0)
+    }
+}
+
+// time join_data spent this frame, join_mesh gets whatever is left of the budget
+#[derive(Resource, Default)]
+pub struct ChunkWorkSpent(Duration);
+
+/// running totals of chunk work thrown away because its chunk left every scanner's range
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WastedChunkWork {
+    /// data and mesh tasks dropped while still running
+    pub cancelled: usize,
+    /// data and mesh tasks that had already finished, but were never applied
+    pub discarded: usize,
+}
+
+impl std::ops::AddAssign for WastedChunkWork {
+    fn add_assign(&mut self, rhs: Self) {
+        self.cancelled += rhs.cancelled;
+        self.discarded += rhs.discarded;
+    }
 }
 
-pub struct ChunkModification(pub IVec3, pub BlockType);
+/// local position inside the chunk, and the block to place there
+pub struct ChunkModification(pub IVec3, pub BlockData);
+
+/// sent by start_modifications for every voxel it changed
+#[derive(Event, Debug, Copy, Clone)]
+pub struct VoxelModified {
+    pub world_pos: IVec3,
+    pub previous: BlockData,
+    pub block: BlockData,
+}
 
 const DIAG_LOAD_DATA_QUEUE: DiagnosticPath = DiagnosticPath::const_new("load_data_queue");
 const DIAG_UNLOAD_DATA_QUEUE: DiagnosticPath = DiagnosticPath::const_new("unload_data_queue");
@@ -111,6 +212,8 @@ const DIAG_UNLOAD_MESH_QUEUE: DiagnosticPath = DiagnosticPath::const_new("unload
 const DIAG_VERTEX_COUNT: DiagnosticPath = DiagnosticPath::const_new("vertex_count");
 const DIAG_MESH_TASKS: DiagnosticPath = DiagnosticPath::const_new("mesh_tasks");
 const DIAG_DATA_TASKS: DiagnosticPath = DiagnosticPath::const_new("data_tasks");
+const DIAG_CANCELLED_TASKS: DiagnosticPath = DiagnosticPath::const_new("cancelled_tasks");
+const DIAG_DISCARDED_TASKS: DiagnosticPath = DiagnosticPath::const_new("discarded_tasks");
 
 fn setup_diagnostics(mut onscreen: ResMut<ScreenDiagnostics>) {
     onscreen
@@ -141,9 +244,21 @@ fn setup_diagnostics(mut onscreen: ResMut<ScreenDiagnostics>) {
         .add("data_tasks".to_string(), DIAG_DATA_TASKS)
         .aggregate(Aggregate::Value)
         .format(|v| format!("{v:0>2.0}"));
+    onscreen
+        .add("cancelled_tasks".to_string(), DIAG_CANCELLED_TASKS)
+        .aggregate(Aggregate::Value)
+        .format(|v| format!("{v:0>5.0}"));
+    onscreen
+        .add("discarded_tasks".to_string(), DIAG_DISCARDED_TASKS)
+        .aggregate(Aggregate::Value)
+        .format(|v| format!("{v:0>5.0}"));
 }
 
-fn diagnostics_count(mut diagnostics: Diagnostics, voxel_engine: Res<VoxelEngine>) {
+fn diagnostics_count(
+    mut diagnostics: Diagnostics,
+    voxel_engine: Res<VoxelEngine>,
+    wasted: Res<WastedChunkWork>,
+) {
     diagnostics.add_measurement(&DIAG_LOAD_DATA_QUEUE, || {
         voxel_engine.load_data_queue.len() as f64
     });
@@ -158,6 +273,8 @@ fn diagnostics_count(mut diagnostics: Diagnostics, voxel_engine: Res<VoxelEngine
     });
     diagnostics.add_measurement(&DIAG_MESH_TASKS, || voxel_engine.mesh_tasks.len() as f64);
     diagnostics.add_measurement(&DIAG_DATA_TASKS, || voxel_engine.data_tasks.len() as f64);
+    diagnostics.add_measurement(&DIAG_CANCELLED_TASKS, || wasted.cancelled as f64);
+    diagnostics.add_measurement(&DIAG_DISCARDED_TASKS, || wasted.discarded as f64);
     diagnostics.add_measurement(&DIAG_VERTEX_COUNT, || {
         voxel_engine
             .vertex_diagnostic
@@ -168,19 +285,75 @@ fn diagnostics_count(mut diagnostics: Diagnostics, voxel_engine: Res<VoxelEngine
 }
 
 impl VoxelEngine {
-    pub fn unload_all_meshes(&mut self, scanner: &Scanner, scanner_transform: &GlobalTransform) {
+    pub fn unload_all_meshes(&mut self, chunk_interest: &ChunkInterest) {
         // stop all any current proccessing
         self.load_mesh_queue.clear();
         // self.unload_mesh_queue.clear();
         self.mesh_tasks.clear();
-        let scan_pos =
-            ((scanner_transform.translation() - Vec3::splat(16.0)) * (1.0 / 32.0)).as_ivec3();
-        for offset in &scanner.mesh_sampling_offsets {
-            let wpos = scan_pos + *offset;
-            self.load_mesh_queue.push(wpos);
-            // self.unload_mesh_queue.push(wpos);
-        }
+        // remesh everything any scanner wants meshed
+        self.load_mesh_queue.extend(chunk_interest.mesh_chunks());
     }
+
+    /// drop queued loads and in flight tasks for chunks no scanner wants anymore,
+    /// dropping a task cancels it so its result is never applied
+    pub fn cancel_unwanted_tasks(&mut self, chunk_interest: &ChunkInterest) -> WastedChunkWork {
+        let mut wasted = WastedChunkWork::default();
+        let mut count = |task_finished: bool| match task_finished {
+            true => wasted.discarded += 1,
+            false => wasted.cancelled += 1,
+        };
+        self.load_data_queue
+            .retain(|p| chunk_interest.wants_data(*p));
+        self.load_mesh_queue
+            .retain(|p| chunk_interest.wants_mesh(*p));
+        self.data_tasks.retain(|p, task| {
+            let wanted = chunk_interest.wants_data(*p);
+            if !wanted {
+                count(task.as_ref().is_some_and(|t| t.is_finished()));
+            }
+            wanted
+        });
+        self.mesh_tasks.retain(|(p, task)| {
+            let wanted = chunk_interest.wants_mesh(*p);
+            if !wanted {
+                count(task.as_ref().is_some_and(|t| t.is_finished()));
+            }
+            wanted
+        });
+        wasted
+    }
+}
+
+/// cancel work for chunks that left the range of every scanner, before it is joined
+pub fn cancel_unwanted_tasks(
+    mut voxel_engine: ResMut<VoxelEngine>,
+    chunk_interest: Option<Res<ChunkInterest>>,
+    mut wasted: ResMut<WastedChunkWork>,
+) {
+    // without scanners there is nothing to measure range against
+    let Some(chunk_interest) = chunk_interest else {
+        return;
+    };
+    *wasted += voxel_engine.cancel_unwanted_tasks(&chunk_interest);
+}
+
+/// sample the block at a world voxel position, None if its chunk isn't loaded
+pub fn get_world_block(
+    world_data: &HashMap<IVec3, Arc<ChunkData>>,
+    world_pos: IVec3,
+) -> Option<&BlockData> {
+    let (chunk_pos, local_pos) = world_voxel_to_chunk(world_pos);
+    let chunk_data = world_data.get(&chunk_pos)?;
+    Some(chunk_data.get_block(vec3_to_index(local_pos, CHUNK_SIZE_I32)))
+}
+
+/// solidity of a voxel from its chunk's occupancy masks, None if the chunk isn't loaded
+pub fn is_world_voxel_solid(
+    world_data: &HashMap<IVec3, Arc<ChunkData>>,
+    world_pos: IVec3,
+) -> Option<bool> {
+    let (chunk_pos, local_pos) = world_voxel_to_chunk(world_pos);
+    Some(world_data.get(&chunk_pos)?.is_solid(local_pos))
 }
 
 impl Default for VoxelEngine {
@@ -200,14 +373,30 @@ impl Default for VoxelEngine {
             meshing_method: MeshingMethod::BinaryGreedyMeshing,
             vertex_diagnostic: HashMap::new(),
             chunk_modifications: HashMap::new(),
+            build_collision_meshes: false,
+            packed_quads: false,
         }
     }
 }
 
+fn scanner_views(
+    scanners: &Query<(&GlobalTransform, &Scanner, Option<&Frustum>)>,
+) -> Vec<ScannerView> {
+    scanners
+        .iter()
+        .map(|(g, scanner, frustum)| ScannerView {
+            chunk_pos: world_to_chunk(g.translation()),
+            frustum: frustum.copied(),
+            travel_direction: scanner.travel_direction,
+        })
+        .collect()
+}
+
 ///! begin data building tasks for chunks in range
 pub fn start_data_tasks(
     mut voxel_engine: ResMut<VoxelEngine>,
-    scanners: Query<&GlobalTransform, With<Scanner>>,
+    scanners: Query<(&GlobalTransform, &Scanner, Option<&Frustum>)>,
+    budget: Res<ChunkWorkBudget>,
 ) {
     let task_pool = AsyncComputeTaskPool::get();
 
@@ -217,18 +406,14 @@ pub fn start_data_tasks(
         ..
     } = voxel_engine.as_mut();
 
-    let scanner_g = scanners.single();
-    let scan_pos = ((scanner_g.translation() - Vec3::splat(16.0)) * (1.0 / 32.0)).as_ivec3();
-    load_data_queue.sort_by(|a, b| {
-        a.distance_squared(scan_pos)
-            .cmp(&b.distance_squared(scan_pos))
-    });
+    // what the scanners look at and move towards comes first
+    let views = scanner_views(&scanners);
+    load_data_queue.sort_by_cached_key(|p| load_priority(*p, &views));
 
-    let tasks_left = (MAX_DATA_TASKS as i32 - data_tasks.len() as i32)
+    let tasks_left = (budget.max_data_tasks as i32 - data_tasks.len() as i32)
         .min(load_data_queue.len() as i32)
         .max(0) as usize;
     for world_pos in load_data_queue.drain(0..tasks_left) {
-        // for world_pos in load_data_queue.drain(0..MAX_DATA_TASKS.min(load_data_queue.len())) {
         // for world_pos in load_data_queue.drain(..) {
         let k = world_pos;
         let task = task_pool.spawn(async move {
@@ -265,8 +450,8 @@ pub fn unload_mesh(mut commands: Commands, mut voxel_engine: ResMut<VoxelEngine>
             continue;
         };
         vertex_diagnostic.remove(&chunk_pos);
-        if let Some(mut entity_commands) = commands.get_entity(chunk_id) {
-            entity_commands.despawn();
+        if let Some(entity_commands) = commands.get_entity(chunk_id) {
+            entity_commands.despawn_recursive();
         }
         // world_data.remove(&chunk_pos);
     }
@@ -276,7 +461,8 @@ pub fn unload_mesh(mut commands: Commands, mut voxel_engine: ResMut<VoxelEngine>
 ///! begin mesh building tasks for chunks in range
 pub fn start_mesh_tasks(
     mut voxel_engine: ResMut<VoxelEngine>,
-    scanners: Query<&GlobalTransform, With<Scanner>>,
+    scanners: Query<(&GlobalTransform, &Scanner, Option<&Frustum>)>,
+    budget: Res<ChunkWorkBudget>,
 ) {
     let task_pool = AsyncComputeTaskPool::get();
 
@@ -286,39 +472,44 @@ pub fn start_mesh_tasks(
         world_data,
         lod,
         meshing_method,
+        build_collision_meshes,
+        packed_quads,
         ..
     } = voxel_engine.as_mut();
 
-    let scanner_g = scanners.single();
-    let scan_pos = ((scanner_g.translation() - Vec3::splat(16.0)) * (1.0 / 32.0)).as_ivec3();
-    load_mesh_queue.sort_by(|a, b| {
-        a.distance_squared(scan_pos)
-            .cmp(&b.distance_squared(scan_pos))
-    });
-    let tasks_left = (MAX_MESH_TASKS as i32 - mesh_tasks.len() as i32)
-        .min(load_mesh_queue.len() as i32)
-        .max(0) as usize;
-    for world_pos in load_mesh_queue.drain(0..tasks_left) {
-        // for world_pos in load_mesh_queue.drain(..) {
-        let Some(chunks_refs) = ChunksRefs::try_new(world_data, world_pos) else {
+    let views = scanner_views(&scanners);
+    load_mesh_queue.sort_by_cached_key(|p| load_priority(*p, &views));
+    // chunks whose neighbours are still generating wait in the queue without using up a task
+    let mut waiting = vec![];
+    let mut queue = load_mesh_queue.drain(..);
+    while mesh_tasks.len() < budget.max_mesh_tasks {
+        let Some(world_pos) = queue.next() else {
+            break;
+        };
+        let Ok(chunks_refs) = ChunksRefs::try_new(world_data, world_pos) else {
+            waiting.push(world_pos);
             continue;
         };
-        let llod = *lod;
-        let task = match meshing_method {
-            MeshingMethod::BinaryGreedyMeshing => task_pool.spawn(async move {
-                crate::greedy_mesher_optimized::build_chunk_mesh(&chunks_refs, llod)
-            }),
-            MeshingMethod::VertexCulled => task_pool.spawn(async move {
-                crate::culled_mesher::build_chunk_mesh_ao(&chunks_refs, llod)
-            }),
+        let options = MeshOptions {
+            lod: *lod,
+            build_collision: *build_collision_meshes,
+            packed_quads: *packed_quads,
         };
+        let mesher = meshing_method.mesher();
+        let task = task_pool.spawn(async move { mesher.build_chunk_mesh(&chunks_refs, &options) });
 
         mesh_tasks.push((world_pos, Some(task)));
     }
+    let rest: Vec<IVec3> = queue.collect();
+    load_mesh_queue.extend(waiting);
+    load_mesh_queue.extend(rest);
 }
 
 // start
-pub fn start_modifications(mut voxel_engine: ResMut<VoxelEngine>) {
+pub fn start_modifications(
+    mut voxel_engine: ResMut<VoxelEngine>,
+    mut modified: EventWriter<VoxelModified>,
+) {
     let VoxelEngine {
         world_data,
         chunk_modifications,
@@ -332,18 +523,14 @@ pub fn start_modifications(mut voxel_engine: ResMut<VoxelEngine>) {
         };
         let new_chunk_data = Arc::make_mut(chunk_data);
         let mut adj_chunk_set = HashSet::new();
-        for ChunkModification(local_pos, block_type) in mods.into_iter() {
-            let i = vec3_to_index(local_pos, 32);
-            if new_chunk_data.voxels.len() == 1 {
-                let mut voxels = vec![];
-                for _ in 0..CHUNK_SIZE_I32 * CHUNK_SIZE_I32 * CHUNK_SIZE_I32 {
-                    voxels.push(BlockData {
-                        block_type: new_chunk_data.voxels[0].block_type,
-                    });
-                }
-                new_chunk_data.voxels = voxels;
-            }
-            new_chunk_data.voxels[i].block_type = block_type;
+        for ChunkModification(local_pos, block) in mods.into_iter() {
+            let i = vec3_to_index(local_pos, CHUNK_SIZE_I32);
+            let previous = new_chunk_data.set_block(i, block);
+            modified.send(VoxelModified {
+                world_pos: pos * CHUNK_SIZE_I32 + local_pos,
+                previous,
+                block,
+            });
             if let Some(edge_chunk) = get_edging_chunk(local_pos) {
                 adj_chunk_set.insert(edge_chunk);
             }
@@ -356,13 +543,24 @@ pub fn start_modifications(mut voxel_engine: ResMut<VoxelEngine>) {
 }
 
 ///! join the chunkdata threads
-pub fn join_data(mut voxel_engine: ResMut<VoxelEngine>) {
+pub fn join_data(
+    mut voxel_engine: ResMut<VoxelEngine>,
+    budget: Res<ChunkWorkBudget>,
+    mut spent: ResMut<ChunkWorkSpent>,
+) {
     let VoxelEngine {
         world_data,
         data_tasks,
         ..
     } = voxel_engine.as_mut();
+    let start = Instant::now();
+    let frame_budget = budget.frame_budget();
+    let mut joined = 0;
     for (world_pos, task_option) in data_tasks.iter_mut() {
+        // leave the rest for next frame, but always make some progress
+        if joined > 0 && start.elapsed() >= frame_budget {
+            break;
+        }
         let Some(mut task) = task_option.take() else {
             // should never happend, because we drop None values later
             warn!("someone modified task?");
@@ -372,10 +570,12 @@ pub fn join_data(mut voxel_engine: ResMut<VoxelEngine>) {
             *task_option = Some(task);
             continue;
         };
+        joined += 1;
 
         world_data.insert(*world_pos, Arc::new(chunk_data));
     }
     data_tasks.retain(|_k, op| op.is_some());
+    spent.0 = start.elapsed();
 }
 
 #[derive(Component)]
@@ -413,6 +613,13 @@ pub fn join_mesh(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     global_chunk_material: Res<GlobalChunkMaterial>,
+    (global_chunk_quad_material, mut chunk_quad_materials, global_smooth_chunk_material): (
+        Res<GlobalChunkQuadMaterial>,
+        ResMut<Assets<ChunkQuadMaterial>>,
+        Res<GlobalSmoothChunkMaterial>,
+    ),
+    budget: Res<ChunkWorkBudget>,
+    spent: Res<ChunkWorkSpent>,
 ) {
     let VoxelEngine {
         mesh_tasks,
@@ -420,7 +627,14 @@ pub fn join_mesh(
         vertex_diagnostic,
         ..
     } = voxel_engine.as_mut();
+    let start = Instant::now();
+    let frame_budget = budget.frame_budget().saturating_sub(spent.0);
+    let mut joined = 0;
     for (world_pos, task_option) in mesh_tasks.iter_mut() {
+        // leave the rest for next frame, but always make some progress
+        if joined > 0 && start.elapsed() >= frame_budget {
+            break;
+        }
         let Some(mut task) = task_option.take() else {
             // should never happend, because we drop None values later
             warn!("someone modified task?");
@@ -431,37 +645,248 @@ pub fn join_mesh(
             *task_option = Some(task);
             continue;
         };
+        joined += 1;
 
-        let Some(mesh) = chunk_mesh_option else {
+        let Some(mut mesh) = chunk_mesh_option else {
             continue;
         };
-        let mut bevy_mesh = Mesh::new(
-            PrimitiveTopology::TriangleList,
-            RenderAssetUsages::RENDER_WORLD,
-        );
-        vertex_diagnostic.insert(*world_pos, mesh.vertices.len() as i32);
-        bevy_mesh.insert_attribute(ATTRIBUTE_VOXEL, mesh.vertices.clone());
-        // bevy_mesh.set_indices(Some(Indices::U32(mesh.indices.clone().into())));
-        bevy_mesh.insert_indices(Indices::U32(mesh.indices.clone().into()));
-        let mesh_handle = meshes.add(bevy_mesh);
-
         if let Some(entity) = chunk_entities.get(world_pos) {
-            commands.entity(*entity).despawn();
+            commands.entity(*entity).despawn_recursive();
         }
 
+        let aabb = Aabb::from_min_max(Vec3::ZERO, Vec3::splat(CHUNK_SIZE_I32 as f32));
+        let transform = Transform::from_translation(world_pos.as_vec3() * CHUNK_SIZE_I32 as f32);
         // spawn chunk entity
-        let chunk_entity = commands
-            .spawn((
-                Aabb::from_min_max(Vec3::ZERO, Vec3::splat(32.0)),
+        let mut chunk_entity = if let Some(smooth) = mesh.smooth.take() {
+            vertex_diagnostic.insert(
+                *world_pos,
+                (smooth.positions.len() + mesh.vertices.len()) as i32,
+            );
+            // fluids stay blocky, drawn by a child with the voxel material
+            let fluids = (!mesh.vertices.is_empty()).then(|| {
+                let vertices = std::mem::take(&mut mesh.vertices);
+                let indices = std::mem::take(&mut mesh.indices);
+                commands
+                    .spawn((
+                        aabb,
+                        MaterialMeshBundle {
+                            mesh: meshes.add(voxel_chunk_mesh(vertices, indices)),
+                            material: global_chunk_material.0.clone(),
+                            ..default()
+                        },
+                    ))
+                    .id()
+            });
+            // the smooth surface passes through voxel centers, up to half a voxel into the next chunk
+            let aabb = Aabb::from_min_max(Vec3::ZERO, Vec3::splat(CHUNK_SIZE_I32 as f32 + 1.0));
+            let mut chunk_entity = commands.spawn((
+                aabb,
+                MaterialMeshBundle {
+                    transform,
+                    mesh: meshes.add(smooth_chunk_mesh(smooth)),
+                    material: global_smooth_chunk_material.0.clone(),
+                    ..default()
+                },
+            ));
+            if let Some(fluids) = fluids {
+                chunk_entity.add_child(fluids);
+            }
+            chunk_entity
+        } else if mesh.quads.is_empty() {
+            vertex_diagnostic.insert(*world_pos, mesh.vertices.len() as i32);
+            let vertices = std::mem::take(&mut mesh.vertices);
+            let indices = std::mem::take(&mut mesh.indices);
+            commands.spawn((
+                aabb,
                 MaterialMeshBundle {
-                    transform: Transform::from_translation(world_pos.as_vec3() * Vec3::splat(32.0)),
-                    mesh: mesh_handle,
+                    transform,
+                    mesh: meshes.add(voxel_chunk_mesh(vertices, indices)),
                     material: global_chunk_material.0.clone(),
                     ..default()
                 },
             ))
-            .id();
-        chunk_entities.insert(*world_pos, chunk_entity);
+        } else {
+            // same vertex count the u32 layout would have used
+            vertex_diagnostic.insert(*world_pos, mesh.quads.len() as i32 * 4);
+            let material = global_chunk_quad_material.0.with_quads(&mesh.quads);
+            commands.spawn((
+                aabb,
+                MaterialMeshBundle {
+                    transform,
+                    mesh: meshes.add(packed_quad_mesh(mesh.quads.len())),
+                    material: chunk_quad_materials.add(material),
+                    ..default()
+                },
+            ))
+        };
+        if let Some(collision) = mesh.collision.take() {
+            chunk_entity.insert(collision);
+        }
+        chunk_entities.insert(*world_pos, chunk_entity.id());
     }
     mesh_tasks.retain(|(_p, op)| op.is_some());
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chunk::ChunkOccupancy, constants::CHUNK_SIZE3, load_area::ChunkArea,
+        rendering::ATTRIBUTE_VOXEL, scanner::ScannerClaims, utils::index_to_ivec3,
+        voxel::BlockType,
+    };
+    use bevy::{ecs::system::RunSystemOnce, tasks::TaskPool};
+
+    // resources join_data and join_mesh run with
+    fn join_world() -> World {
+        let mut world = World::new();
+        world.init_resource::<Assets<Mesh>>();
+        world.init_resource::<Assets<ChunkQuadMaterial>>();
+        world.init_resource::<ChunkWorkBudget>();
+        world.init_resource::<ChunkWorkSpent>();
+        world.insert_resource(GlobalChunkMaterial(Handle::default()));
+        world.insert_resource(GlobalSmoothChunkMaterial(Handle::default()));
+        world.insert_resource(GlobalChunkQuadMaterial(ChunkQuadMaterial {
+            reflectance: 0.0,
+            perceptual_roughness: 0.0,
+            metallic: 0.0,
+            quads: vec![],
+        }));
+        world
+    }
+
+    fn finished_task<T: Send + 'static>(value: T) -> Task<T> {
+        let task_pool = AsyncComputeTaskPool::get_or_init(TaskPool::default);
+        let task = task_pool.spawn(async move { value });
+        while !task.is_finished() {
+            std::thread::yield_now();
+        }
+        task
+    }
+
+    #[test]
+    fn out_of_range_tasks_are_cancelled() {
+        let task_pool = AsyncComputeTaskPool::get_or_init(TaskPool::default);
+        let mut chunk_interest = ChunkInterest::default();
+        chunk_interest.update_claims(
+            Entity::from_raw(0),
+            ScannerClaims {
+                data: ChunkArea::new(IVec3::ZERO, 1),
+                mesh: ChunkArea::new(IVec3::ZERO, 0),
+            },
+        );
+        let near = IVec3::ZERO;
+        let edge = IVec3::X;
+        let far = IVec3::X * 5;
+
+        let mut voxel_engine = VoxelEngine::default();
+        for pos in [near, edge, far] {
+            let task = task_pool.spawn(async { ChunkData::filled(BlockType::Air) });
+            voxel_engine.data_tasks.insert(pos, Some(task));
+            voxel_engine.load_data_queue.push(pos);
+            voxel_engine.load_mesh_queue.push(pos);
+        }
+        // never resolves, so it is always counted as cancelled
+        let pending = task_pool.spawn(future::pending::<Option<ChunkMesh>>());
+        voxel_engine.mesh_tasks.push((edge, Some(pending)));
+        voxel_engine
+            .mesh_tasks
+            .push((far, Some(finished_task(None))));
+
+        let wasted = voxel_engine.cancel_unwanted_tasks(&chunk_interest);
+        assert_eq!(wasted.cancelled + wasted.discarded, 3);
+        assert!(wasted.cancelled >= 1 && wasted.discarded >= 1);
+        let mut data_tasks: Vec<_> = voxel_engine.data_tasks.keys().copied().collect();
+        data_tasks.sort_by_key(|p| p.x);
+        assert_eq!(data_tasks, [near, edge]);
+        assert!(voxel_engine.mesh_tasks.is_empty());
+        assert_eq!(voxel_engine.load_data_queue, [near, edge]);
+        assert_eq!(voxel_engine.load_mesh_queue, [near]);
+    }
+
+    #[test]
+    fn modifications_update_occupancy() {
+        let mut world = World::new();
+        world.init_resource::<Events<VoxelModified>>();
+        let mut voxel_engine = VoxelEngine::default();
+        let chunk = Arc::new(ChunkData::filled(BlockType::Air));
+        voxel_engine.world_data.insert(IVec3::ZERO, chunk);
+        let dirt = IVec3::new(1, 2, 3);
+        let water = IVec3::new(4, 0, 0);
+        voxel_engine.chunk_modifications.insert(
+            IVec3::ZERO,
+            vec![
+                ChunkModification(dirt, BlockData::from(BlockType::Dirt)),
+                ChunkModification(water, BlockData::from(BlockType::Water)),
+            ],
+        );
+        world.insert_resource(voxel_engine);
+        world.run_system_once(start_modifications);
+
+        let chunk = &world.resource::<VoxelEngine>().world_data[&IVec3::ZERO];
+        let occupancy = chunk.occupancy().unwrap();
+        assert!(occupancy.is_solid(dirt));
+        assert!(!occupancy.is_solid(water));
+        assert_eq!(*occupancy, ChunkOccupancy::from_voxels(chunk.voxels()));
+    }
+
+    #[test]
+    fn smooth_chunks_draw_fluids_as_a_child() {
+        let mut world = join_world();
+
+        // a sand floor with water on top
+        let mut voxels = vec![BlockData::default(); CHUNK_SIZE3];
+        for (i, voxel) in voxels.iter_mut().enumerate() {
+            match index_to_ivec3(i as i32).y {
+                0 => *voxel = BlockType::Sand.into(),
+                1 => *voxel = BlockType::Water.into(),
+                _ => {}
+            }
+        }
+        let chunks_refs = ChunksRefs::with_middle(ChunkData::new(voxels));
+        let task =
+            finished_task(SmoothMesher.build_chunk_mesh(&chunks_refs, &MeshOptions::default()));
+        let mut voxel_engine = VoxelEngine::default();
+        voxel_engine.mesh_tasks.push((IVec3::ZERO, Some(task)));
+        world.insert_resource(voxel_engine);
+        world.run_system_once(join_mesh);
+
+        let entity = world.resource::<VoxelEngine>().chunk_entities[&IVec3::ZERO];
+        let children = world.get::<Children>(entity).expect("fluids are a child");
+        assert_eq!(children.len(), 1);
+        let meshes = world.resource::<Assets<Mesh>>();
+        let fluid_mesh = world.get::<Handle<Mesh>>(children[0]).unwrap();
+        assert!(meshes
+            .get(fluid_mesh)
+            .unwrap()
+            .attribute(ATTRIBUTE_VOXEL)
+            .is_some());
+    }
+
+    #[test]
+    fn spent_budget_joins_one_task_per_frame() {
+        let mut world = join_world();
+        world.insert_resource(ChunkWorkBudget {
+            frame_budget_ms: 0.0,
+            ..default()
+        });
+        let mut voxel_engine = VoxelEngine::default();
+        for x in 0..3 {
+            let data = finished_task(ChunkData::filled(BlockType::Air));
+            voxel_engine.data_tasks.insert(IVec3::X * x, Some(data));
+            voxel_engine
+                .mesh_tasks
+                .push((IVec3::X * x, Some(finished_task(None))));
+        }
+        world.insert_resource(voxel_engine);
+
+        for joined in 1..=3 {
+            world.run_system_once(join_data);
+            world.run_system_once(join_mesh);
+            let voxel_engine = world.resource::<VoxelEngine>();
+            assert_eq!(voxel_engine.world_data.len(), joined);
+            assert_eq!(voxel_engine.data_tasks.len(), 3 - joined);
+            assert_eq!(voxel_engine.mesh_tasks.len(), 3 - joined);
+        }
+    }
+}